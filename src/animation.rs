@@ -1,11 +1,792 @@
-use gdnative::api::AnimationPlayer;
+//! Animation helpers
+//!
+//! ## Animation player
+//!
+//! ```ignore
+//! player.play_then("attack", || gdp!("attack done"))?;
+//! player.play_queued(&["draw", "aim", "fire"]);
+//!
+//! let handle = player.wait_for_animation("open")?;
+//! player.play_default("open");
+//! // Later
+//! if handle.is_done() { ... }
+//! ```
+//!
+//! Method track calls can be received as Rust events with `AnimationEvents`,
+//! which has to be registered:
+//!
+//! ```ignore
+//! fn init(handle: init::InitHandle) {
+//!     handle.add_class::<gdextras::animation::AnimationEvents>();
+//! }
+//! ```
+//!
+//! ## Animated sprite
+//!
+//! ```ignore
+//! self.velocity = owner.move_and_slide_default(self.velocity, UP_2D);
+//! let facing = self.facing.update(self.velocity);
+//!
+//! // Plays `run_left`, `run_right` flipped, or `run`
+//! sprite.play_directional("run", facing)?;
+//! sprite.on_frame("run", 2, || footstep())?;
+//! ```
+//!
+//! ## Animation tree
+//!
+//! ```ignore
+//! let tree = owner.get_and_cast::<AnimationTree>("AnimationTree");
+//! tree.travel("Run")?;
+//! tree.set_blend_position("Move", velocity.normalize())?;
+//! tree.set_condition("on_floor", true)?;
+//! ```
+//!
+//! ## Animation controller
+//!
+//! Map gameplay states to animations, e.g. from a `StateMachine` hook:
+//!
+//! ```ignore
+//! let mut animations = AnimationController::new(0.1);
+//! animations.add(PlayerState::Idle, "idle", 0.2);
+//! animations.add(PlayerState::Run, "run", 0.1);
+//! animations.add(PlayerState::Jump, "jump", 0.0);
+//!
+//! // Only plays the animation if the state changed
+//! animations.play(player, self.fsm.current())?;
+//! ```
+use gdnative::api::{
+    AnimatedSprite, AnimationNodeStateMachinePlayback, AnimationPlayer, AnimationTree, Node, Object,
+};
+use gdnative::init::property::{ExportInfo, Usage};
+use gdnative::init::{ClassBuilder, Signal, SignalArgument};
+use gdnative::{methods, NativeClass, Ref, ToVariant, Variant, VariantType, Vector2};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Debug;
+use std::future::Future;
+use std::hash::Hash;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
 
+use crate::callback::connect_callback;
+use crate::error::{Error, Result};
+use crate::movement::Direction;
+use crate::thread::ThreadBound;
+
+/// Path of the playback of the root state machine
+pub const ROOT_PLAYBACK: &str = "parameters/playback";
+
+// -----------------------------------------------------------------------------
+//     - Animation player -
+// -----------------------------------------------------------------------------
+/// Completion callbacks and handles require the `Callback` class to be registered,
+/// see the `callback` module.
+///
+/// Looping animations never finish, so their callbacks are never called.
 pub trait AnimationPlayerExt {
     fn play_default(&mut self, animation_name: &str);
+
+    /// Play an animation, then call `f` once it finishes.
+    fn play_then<F>(&self, animation_name: &str, f: F) -> Result<()>
+    where
+        F: FnOnce() + Send + 'static;
+
+    /// Call `f` the next time the animation finishes.
+    fn on_animation_finished<F>(&self, animation_name: &str, f: F) -> Result<()>
+    where
+        F: FnOnce() + Send + 'static;
+
+    /// Get a handle that is done the next time the animation finishes.
+    fn wait_for_animation(&self, animation_name: &str) -> Result<AnimationHandle>;
+
+    /// Play the first animation, and queue the rest.
+    fn play_queued(&self, animation_names: &[&str]);
+
+    fn play_backwards_default(&self, animation_name: &str);
+
+    /// Play an animation starting at `time` seconds.
+    fn play_from(&self, animation_name: &str, time: f64);
+
+    /// Play an animation at `speed` times the normal speed.
+    fn play_with_speed(&self, animation_name: &str, speed: f64);
 }
 
 impl AnimationPlayerExt for AnimationPlayer {
     fn play_default(&mut self, animation_name: &str) {
         self.play(animation_name.into(), -1., 1., false)
     }
+
+    fn play_then<F>(&self, animation_name: &str, f: F) -> Result<()>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.on_animation_finished(animation_name, f)?;
+        self.play(animation_name.into(), -1., 1., false);
+        Ok(())
+    }
+
+    fn on_animation_finished<F>(&self, animation_name: &str, f: F) -> Result<()>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let name = animation_name.to_string();
+        let callback: Arc<Mutex<Option<Ref<Node>>>> = Arc::default();
+        let callback_node = callback.clone();
+        let mut f = Some(f);
+
+        // Other animations can finish first, so the callback frees itself
+        // instead of being a oneshot connection
+        let node = connect_callback(
+            self.upcast::<Object>(),
+            "animation_finished",
+            self.upcast::<Node>(),
+            false,
+            move |finished| {
+                if finished.try_to_string().as_deref() != Some(name.as_str()) {
+                    return;
+                }
+
+                if let Some(f) = f.take() {
+                    f();
+                }
+
+                let node = callback_node.lock().expect("callback poisoned").take();
+                if let Some(node) = node {
+                    unsafe { node.assume_safe() }.queue_free();
+                }
+            },
+        )?;
+
+        *callback.lock().expect("callback poisoned") = Some(node);
+        Ok(())
+    }
+
+    fn wait_for_animation(&self, animation_name: &str) -> Result<AnimationHandle> {
+        let state = Arc::new(HandleState::default());
+        let handle = AnimationHandle {
+            state: state.clone(),
+        };
+
+        self.on_animation_finished(animation_name, move || state.finish())?;
+        Ok(handle)
+    }
+
+    fn play_queued(&self, animation_names: &[&str]) {
+        let (first, rest) = match animation_names.split_first() {
+            Some(names) => names,
+            None => return,
+        };
+
+        self.play((*first).into(), -1., 1., false);
+        for name in rest {
+            self.queue((*name).into());
+        }
+    }
+
+    fn play_backwards_default(&self, animation_name: &str) {
+        self.play_backwards(animation_name.into(), -1.)
+    }
+
+    fn play_from(&self, animation_name: &str, time: f64) {
+        self.play(animation_name.into(), -1., 1., false);
+        self.seek(time, true);
+    }
+
+    fn play_with_speed(&self, animation_name: &str, speed: f64) {
+        self.play(animation_name.into(), -1., speed, false)
+    }
+}
+
+#[derive(Default)]
+struct HandleState {
+    done: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+}
+
+impl HandleState {
+    fn finish(&self) {
+        self.done.store(true, Ordering::Release);
+        if let Some(waker) = self.waker.lock().expect("waker poisoned").take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Handle to an animation that is playing.
+/// Poll `is_done`, or await it.
+pub struct AnimationHandle {
+    state: Arc<HandleState>,
+}
+
+impl AnimationHandle {
+    pub fn is_done(&self) -> bool {
+        self.state.done.load(Ordering::Acquire)
+    }
+}
+
+impl Future for AnimationHandle {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        if !self.is_done() {
+            *self.state.waker.lock().expect("waker poisoned") = Some(cx.waker().clone());
+
+            // The animation could have finished before the waker was stored
+            if !self.is_done() {
+                return Poll::Pending;
+            }
+        }
+
+        Poll::Ready(())
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Animation events -
+// -----------------------------------------------------------------------------
+/// An event sent from an animation method track.
+pub trait AnimationEvent: Sized {
+    /// Parse an event from its name and argument.
+    /// Return `None` to ignore the event.
+    fn parse(name: &str, arg: &Variant) -> Option<Self>;
+}
+
+/// Collects calls from animation method tracks.
+///
+/// Add a node with this script to the scene, and add a method track calling
+/// `event` on it with the event name and an optional argument.
+/// Events are also emitted with the `animation_event(name, arg)` signal.
+///
+/// ```ignore
+/// enum PlayerEvent {
+///     Footstep,
+///     Hit { damage: i64 },
+/// }
+///
+/// impl AnimationEvent for PlayerEvent {
+///     fn parse(name: &str, arg: &Variant) -> Option<Self> {
+///         match name {
+///             "footstep" => Some(PlayerEvent::Footstep),
+///             "hit" => Some(PlayerEvent::Hit { damage: arg.try_to_i64()? }),
+///             _ => None,
+///         }
+///     }
+/// }
+///
+/// let events = owner.get_and_cast::<Node>("AnimationEvents");
+/// for event in drain_animation_events::<PlayerEvent>(events)? {
+///     ...
+/// }
+/// ```
+#[derive(NativeClass)]
+#[inherit(Node)]
+#[register_with(Self::register)]
+pub struct AnimationEvents {
+    queue: ThreadBound<VecDeque<(String, Variant)>>,
+}
+
+#[methods]
+impl AnimationEvents {
+    fn new(_owner: &Node) -> Self {
+        Self {
+            queue: ThreadBound::new(VecDeque::new()),
+        }
+    }
+
+    fn register(builder: &ClassBuilder<Self>) {
+        builder.add_signal(Signal {
+            name: "animation_event",
+            args: &[
+                SignalArgument {
+                    name: "name",
+                    default: Variant::new(),
+                    export_info: ExportInfo::new(VariantType::GodotString),
+                    usage: Usage::DEFAULT,
+                },
+                SignalArgument {
+                    name: "arg",
+                    default: Variant::new(),
+                    export_info: ExportInfo::new(VariantType::Nil),
+                    usage: Usage::DEFAULT,
+                },
+            ],
+        });
+    }
+
+    /// Called by method tracks.
+    #[export]
+    pub fn event(&mut self, owner: &Node, name: String, #[opt] arg: Variant) {
+        owner.emit_signal("animation_event".into(), &[name.to_variant(), arg.clone()]);
+        self.queue.push_back((name, arg));
+    }
+
+    /// Take all queued events.
+    /// Events that `E` does not parse are dropped.
+    pub fn drain<E: AnimationEvent>(&mut self) -> Vec<E> {
+        self.queue
+            .drain(..)
+            .filter_map(|(name, arg)| E::parse(&name, &arg))
+            .collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.queue.clear();
+    }
+}
+
+/// Take all queued events from a node with the `AnimationEvents` script.
+pub fn drain_animation_events<E: AnimationEvent>(node: &Node) -> Result<Vec<E>> {
+    let name = node.get_name().to_string();
+    let node = unsafe { node.assume_shared().assume_safe() };
+
+    node.cast_instance::<AnimationEvents>()
+        .ok_or_else(|| Error::MissingScript(name.clone()))?
+        .map_mut(|events, _| events.drain())
+        .map_err(|_| Error::Borrow(name))
+}
+
+// -----------------------------------------------------------------------------
+//     - Animated sprite -
+// -----------------------------------------------------------------------------
+/// Directional animations are named with a direction suffix, e.g. `run_left`
+/// or `run_down_left`, see `Direction::suffix`.
+///
+/// When a directional animation is missing it is resolved in this order:
+/// 1. The mirrored direction, flipped horizontally (`run_right` for `run_left`)
+/// 2. For diagonals, the horizontal direction, or its mirror (`run_left`, `run_right`)
+/// 3. For diagonals, the vertical direction (`run_down`)
+/// 4. The animation without a suffix (`run`)
+pub trait AnimatedSpriteExt {
+    fn has_animation(&self, animation_name: &str) -> bool;
+
+    /// Find the animation to play for a direction,
+    /// and whether it should be flipped horizontally.
+    fn resolve_directional(
+        &self,
+        animation_name: &str,
+        direction: Direction,
+    ) -> Option<(String, bool)>;
+
+    /// Play an animation facing a direction.
+    /// Sets `flip_h`, and keeps playing if the animation is already playing.
+    fn play_directional(&self, animation_name: &str, direction: Direction) -> Result<()>;
+
+    /// Play an animation facing the direction of a velocity,
+    /// e.g. the velocity returned by `Move2D::move_and_slide_default`.
+    /// Returns the direction, or `None` for a zero velocity, in which case
+    /// nothing is played. Use `Facing` to keep the direction when standing still.
+    fn play_velocity(&self, animation_name: &str, velocity: Vector2) -> Result<Option<Direction>> {
+        match Direction::from_vector(velocity) {
+            Some(direction) => {
+                self.play_directional(animation_name, direction)?;
+                Ok(Some(direction))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// The current animation without its direction suffix.
+    fn base_animation(&self) -> String;
+
+    /// Call `f` every time `frame` of the animation is shown.
+    /// Directional variants of the animation are included,
+    /// so `on_frame("run", 2, ..)` is called for both `run_left` and `run_right`.
+    ///
+    /// Free the returned node to stop the calls.
+    fn on_frame<F>(&self, animation_name: &str, frame: i64, f: F) -> Result<Ref<Node>>
+    where
+        F: FnMut() + Send + 'static;
+}
+
+impl AnimatedSpriteExt for AnimatedSprite {
+    fn has_animation(&self, animation_name: &str) -> bool {
+        self.get_sprite_frames()
+            .map(|frames| unsafe { frames.assume_safe() }.has_animation(animation_name.into()))
+            .unwrap_or(false)
+    }
+
+    fn resolve_directional(
+        &self,
+        animation_name: &str,
+        direction: Direction,
+    ) -> Option<(String, bool)> {
+        directional_candidates(animation_name, direction)
+            .into_iter()
+            .find(|(name, _)| self.has_animation(name))
+    }
+
+    fn play_directional(&self, animation_name: &str, direction: Direction) -> Result<()> {
+        let (name, flip) = self
+            .resolve_directional(animation_name, direction)
+            .ok_or_else(|| Error::AnimationNotFound(animation_name.to_string()))?;
+
+        self.set_flip_h(flip);
+        self.play(name.into(), false);
+        Ok(())
+    }
+
+    fn base_animation(&self) -> String {
+        strip_direction(&self.get_animation().to_string()).to_string()
+    }
+
+    fn on_frame<F>(&self, animation_name: &str, frame: i64, mut f: F) -> Result<Ref<Node>>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        let name = animation_name.to_string();
+        let sprite = unsafe { self.assume_shared() };
+
+        connect_callback(
+            self.upcast::<Object>(),
+            "frame_changed",
+            self.upcast::<Node>(),
+            false,
+            move |_| {
+                let sprite = unsafe { sprite.assume_safe() };
+                if sprite.get_frame() != frame {
+                    return;
+                }
+
+                if strip_direction(&sprite.get_animation().to_string()) == name {
+                    f();
+                }
+            },
+        )
+    }
+}
+
+// Animation names to try for a direction, with whether to flip the sprite:
+// the exact direction, its horizontal mirror, the four-way parts of a diagonal,
+// then the animation without a direction
+fn directional_candidates(animation_name: &str, direction: Direction) -> Vec<(String, bool)> {
+    let mut candidates = vec![(direction, false), (direction.flip_h(), true)];
+    if direction.is_diagonal() {
+        let (horizontal, vertical) = direction.split();
+        if let Some(horizontal) = horizontal {
+            candidates.push((horizontal, false));
+            candidates.push((horizontal.flip_h(), true));
+        }
+        if let Some(vertical) = vertical {
+            candidates.push((vertical, false));
+        }
+    }
+
+    candidates
+        .into_iter()
+        .filter(|(candidate, flip)| !(*flip && *candidate == candidate.flip_h()))
+        .map(|(candidate, flip)| (format!("{}_{}", animation_name, candidate.suffix()), flip))
+        .chain(std::iter::once((animation_name.to_string(), false)))
+        .collect()
+}
+
+// Remove the direction suffix from an animation name, e.g. `run_down_left` to `run`
+fn strip_direction(animation_name: &str) -> &str {
+    Direction::ALL
+        .iter()
+        .filter_map(|direction| {
+            animation_name
+                .strip_suffix(direction.suffix())
+                .and_then(|name| name.strip_suffix('_'))
+        })
+        .min_by_key(|name| name.len())
+        .unwrap_or(animation_name)
+}
+
+// -----------------------------------------------------------------------------
+//     - Animation tree -
+// -----------------------------------------------------------------------------
+/// Parameters are named without the `parameters/` prefix,
+/// e.g. `Move/blend_position`.
+pub trait AnimationTreeExt {
+    /// Get the playback of a state machine node.
+    /// `path` is the full parameter path, e.g. `parameters/Locomotion/playback`.
+    fn playback(&self, path: &str) -> Result<Ref<AnimationNodeStateMachinePlayback>>;
+
+    /// Travel to a state in the root state machine.
+    fn travel(&self, state: &str) -> Result<()> {
+        self.travel_in(ROOT_PLAYBACK, state)
+    }
+
+    /// Travel to a state in the state machine with the playback at `path`.
+    fn travel_in(&self, path: &str, state: &str) -> Result<()> {
+        let playback = self.playback(path)?;
+        unsafe { playback.assume_safe() }.travel(state.into());
+        Ok(())
+    }
+
+    /// The current state of the root state machine.
+    fn current_state(&self) -> Result<String> {
+        self.current_state_in(ROOT_PLAYBACK)
+    }
+
+    /// The current state of the state machine with the playback at `path`.
+    fn current_state_in(&self, path: &str) -> Result<String> {
+        let playback = self.playback(path)?;
+        Ok(unsafe { playback.assume_safe() }
+            .get_current_node()
+            .to_string())
+    }
+
+    /// Get a parameter.
+    fn parameter(&self, name: &str) -> Result<Variant>;
+
+    /// Set a parameter.
+    /// Fails if the parameter does not exist, or if the value is of another type.
+    fn set_parameter<T: ToVariant>(&self, name: &str, value: T) -> Result<()>;
+
+    /// Set the blend position of a `BlendSpace2D`.
+    fn set_blend_position(&self, blend_space: &str, position: Vector2) -> Result<()> {
+        self.set_parameter(&format!("{}/blend_position", blend_space), position)
+    }
+
+    /// Set the blend position of a `BlendSpace1D`.
+    fn set_blend_position_1d(&self, blend_space: &str, position: f64) -> Result<()> {
+        self.set_parameter(&format!("{}/blend_position", blend_space), position)
+    }
+
+    /// Set a state machine transition condition.
+    fn set_condition(&self, condition: &str, value: bool) -> Result<()> {
+        self.set_parameter(&format!("conditions/{}", condition), value)
+    }
+}
+
+impl AnimationTreeExt for AnimationTree {
+    fn playback(&self, path: &str) -> Result<Ref<AnimationNodeStateMachinePlayback>> {
+        self.get(path.into())
+            .try_to_object::<AnimationNodeStateMachinePlayback>()
+            .ok_or_else(|| Error::ParameterNotFound(path.to_string()))
+    }
+
+    fn parameter(&self, name: &str) -> Result<Variant> {
+        let path = format!("parameters/{}", name);
+        let value = self.get(path.as_str().into());
+        match value.get_type() {
+            VariantType::Nil => Err(Error::ParameterNotFound(path)),
+            _ => Ok(value),
+        }
+    }
+
+    fn set_parameter<T: ToVariant>(&self, name: &str, value: T) -> Result<()> {
+        let expected = self.parameter(name)?.get_type();
+        let value = value.to_variant();
+
+        // Numbers are converted, as e.g. a blend position is a float
+        let value = match (expected, value.get_type()) {
+            (VariantType::F64, VariantType::I64) => {
+                Variant::from_f64(value.try_to_i64().unwrap_or_default() as f64)
+            }
+            (expected, actual) if expected != actual => {
+                return Err(Error::ParameterType {
+                    name: name.to_string(),
+                    expected,
+                })
+            }
+            _ => value,
+        };
+
+        let path = format!("parameters/{}", name);
+        self.set(path.as_str().into(), value);
+        Ok(())
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Animation controller -
+// -----------------------------------------------------------------------------
+struct Mapping {
+    animation: String,
+    crossfade: f64,
+}
+
+/// Map states to animations, with a crossfade time per animation.
+pub struct AnimationController<S> {
+    animations: HashMap<S, Mapping>,
+    default_crossfade: f64,
+    current: Option<S>,
+}
+
+impl<S: Copy + Eq + Hash + Debug> AnimationController<S> {
+    /// `default_crossfade` is used for states added with `add_default`.
+    pub fn new(default_crossfade: f64) -> Self {
+        Self {
+            animations: HashMap::new(),
+            default_crossfade,
+            current: None,
+        }
+    }
+
+    /// Map a state to an animation, crossfading for `crossfade` seconds.
+    pub fn add(&mut self, state: S, animation: &str, crossfade: f64) {
+        self.animations.insert(
+            state,
+            Mapping {
+                animation: animation.to_string(),
+                crossfade,
+            },
+        );
+    }
+
+    /// Map a state to an animation with the default crossfade time.
+    pub fn add_default(&mut self, state: S, animation: &str) {
+        self.add(state, animation, self.default_crossfade);
+    }
+
+    /// The state of the last played animation.
+    pub fn current(&self) -> Option<S> {
+        self.current
+    }
+
+    /// The animation name of a state.
+    pub fn animation(&self, state: S) -> Option<&str> {
+        self.animations.get(&state).map(|m| m.animation.as_str())
+    }
+
+    /// Play the animation of the state on an `AnimationPlayer`.
+    /// Returns false if the state did not change.
+    pub fn play(&mut self, player: &AnimationPlayer, state: S) -> Result<bool> {
+        if self.current == Some(state) {
+            return Ok(false);
+        }
+
+        let mapping = self.mapping(state)?;
+        if !player.has_animation(mapping.animation.as_str().into()) {
+            return Err(Error::AnimationNotFound(mapping.animation.clone()));
+        }
+
+        player.play(
+            mapping.animation.as_str().into(),
+            mapping.crossfade,
+            1.0,
+            false,
+        );
+        self.current = Some(state);
+        Ok(true)
+    }
+
+    /// Travel to the animation of the state in the root state machine of an `AnimationTree`.
+    /// The crossfade is set by the transitions in the tree.
+    /// Returns false if the state did not change.
+    pub fn travel(&mut self, tree: &AnimationTree, state: S) -> Result<bool> {
+        if self.current == Some(state) {
+            return Ok(false);
+        }
+
+        tree.travel(&self.mapping(state)?.animation)?;
+        self.current = Some(state);
+        Ok(true)
+    }
+
+    /// Forget the current state, so the next `play` or `travel` always starts an animation.
+    pub fn reset(&mut self) {
+        self.current = None;
+    }
+
+    fn mapping(&self, state: S) -> Result<&Mapping> {
+        self.animations
+            .get(&state)
+            .ok_or_else(|| Error::AnimationNotFound(format!("{:?}", state)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(available: &[&str], animation_name: &str, direction: Direction) -> (String, bool) {
+        directional_candidates(animation_name, direction)
+            .into_iter()
+            .find(|(name, _)| available.contains(&name.as_str()))
+            .expect("no candidate")
+    }
+
+    #[test]
+    fn candidates_for_diagonal() {
+        let names = directional_candidates("run", Direction::DownLeft);
+        assert_eq!(
+            names,
+            [
+                ("run_down_left".to_string(), false),
+                ("run_down_right".to_string(), true),
+                ("run_left".to_string(), false),
+                ("run_right".to_string(), true),
+                ("run_down".to_string(), false),
+                ("run".to_string(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn candidates_for_vertical_are_not_flipped() {
+        let names = directional_candidates("run", Direction::Up);
+        assert_eq!(
+            names,
+            [("run_up".to_string(), false), ("run".to_string(), false)]
+        );
+    }
+
+    #[test]
+    fn resolve_prefers_exact_direction() {
+        let available = ["run", "run_left", "run_up_left", "run_up_right"];
+        assert_eq!(
+            resolve(&available, "run", Direction::UpLeft),
+            ("run_up_left".to_string(), false)
+        );
+    }
+
+    #[test]
+    fn resolve_mirrors_direction() {
+        let available = ["run", "run_right", "run_up_right"];
+        assert_eq!(
+            resolve(&available, "run", Direction::Left),
+            ("run_right".to_string(), true)
+        );
+        assert_eq!(
+            resolve(&available, "run", Direction::UpLeft),
+            ("run_up_right".to_string(), true)
+        );
+    }
+
+    #[test]
+    fn resolve_falls_back_to_four_way() {
+        let available = ["run", "run_right", "run_up", "run_down"];
+        assert_eq!(
+            resolve(&available, "run", Direction::UpRight),
+            ("run_right".to_string(), false)
+        );
+        assert_eq!(
+            resolve(&available, "run", Direction::DownLeft),
+            ("run_right".to_string(), true)
+        );
+
+        let available = ["run", "run_up"];
+        assert_eq!(
+            resolve(&available, "run", Direction::UpLeft),
+            ("run_up".to_string(), false)
+        );
+    }
+
+    #[test]
+    fn resolve_falls_back_to_bare_name() {
+        let available = ["run", "run_up"];
+        assert_eq!(
+            resolve(&available, "run", Direction::Down),
+            ("run".to_string(), false)
+        );
+    }
+
+    #[test]
+    fn strip_direction_suffixes() {
+        assert_eq!(strip_direction("run_left"), "run");
+        assert_eq!(strip_direction("run_down_left"), "run");
+        assert_eq!(strip_direction("run_up_right"), "run");
+        assert_eq!(strip_direction("wall_slide_down"), "wall_slide");
+    }
+
+    #[test]
+    fn strip_direction_keeps_names_without_suffix() {
+        assert_eq!(strip_direction("run"), "run");
+        assert_eq!(strip_direction("upright"), "upright");
+        assert_eq!(strip_direction("left"), "left");
+    }
 }
@@ -1,10 +1,12 @@
 //! Audio helper
 //! Example usage:
 //!
-//!```rust
+//! ```ignore
 //! // lib.rs
 //! fn init(handle: init::InitHandle) {
-//!     handle.add_class::<gdextras::audio::Player>();
+//!     handle.add_class::<gdextras::audio::AudioPlayer>();
+//!     handle.add_class::<gdextras::audio::AudioPlayer2D>();
+//!     handle.add_class::<gdextras::audio::AudioPlayer3D>();
 //! }
 //!
 //! // world.rs
@@ -18,55 +20,407 @@
 //!
 //! #[methods]
 //! impl World {
-//!     pub fn _init(_owner: Node2D) -> Self {
+//!     fn new(_owner: &Node2D) -> Self {
 //!         Self {
 //!             sfx_map: SoundBank::new(),
 //!         }
 //!     }
 //!
 //!     #[export]
-//!     pub fn _ready(&mut self, owner: Node2D) {
-//!         self.sfx_map.insert(Sound::Gunshot, "res://sfx/boink.wav");
-//!         self.sfx_map.insert(Sound::Rifle, "res://sfx/blip.wav");
+//!     fn _ready(&mut self, _owner: &Node2D) {
+//!         self.sfx_map.insert(Sound::Gunshot, "res://sfx/boink.wav").log_err();
+//!         self.sfx_map.insert(Sound::Rifle, "res://sfx/blip.wav").log_err();
 //!     }
 //!
-//!     pub fn play_audio(&self, owner: Node2D, sound: Sound) -> Option<()> {
-//!         let stream = self.sfx_map.get(sound)?;
-//!         unsafe {
-//!             play_audio_stream(owner.to_node(), stream);
-//!         }
-//!
-//!         Some(())
+//!     pub fn play_audio(&self, owner: &Node2D, sound: Sound) -> Option<()> {
+//!         let stream = self.sfx_map.get(&sound)?;
+//!         play_audio_stream(owner.upcast(), stream, Some("SFX")).ok()
 //!     }
 //! }
 //! ```
-use gdnative::api::{AudioStream, AudioStreamPlayer, ResourceLoader};
-use gdnative::VariantArray;
+//!
+//! A key can hold several variations, with random pitch and volume and a
+//! limit on how many voices play at once:
+//!
+//! ```ignore
+//! let set = SoundSet::load(&["res://sfx/shot_1.wav", "res://sfx/shot_2.wav"])?
+//!     .with_pitch(0.9, 1.1)
+//!     .with_max_voices(4, StealPolicy::Oldest);
+//! self.sfx_map.insert_set(Sound::Gunshot, set);
+//! self.sfx_map.play(owner.upcast(), &Sound::Gunshot, Some("SFX"));
+//!
+//! // In `_process`, so the cooldown and voice limit follow engine time
+//! self.sfx_map.advance(delta);
+//! ```
 use gdnative::api::{
-    godot_error, godot_wrap_method, godot_wrap_method_inner, godot_wrap_method_parameter_count,
-    methods, NativeClass, InputEvent
+    AudioStream, AudioStreamPlayer, AudioStreamPlayer2D, AudioStreamPlayer3D, ConfigFile, Node,
+    Node2D, Spatial,
 };
+use gdnative::{
+    methods, Instance, Map, NativeClass, Ref, RefInstance, Shared, SubClass, TRef, Unique, Variant,
+    VariantArray, Vector2, Vector3,
+};
+use rand::Rng;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::hash::Hash;
+use std::str::FromStr;
+
+use crate::error::{Error, GdResultExt, Result};
+use crate::gd_err;
+use crate::pool::{NodePool, ReleaseQueue};
+use crate::resource::load_resource;
+use crate::thread::ThreadBound;
+
+/// Bus used when no bus is given
+pub const DEFAULT_BUS: &str = "Master";
+
+// -----------------------------------------------------------------------------
+//     - Sound set -
+// -----------------------------------------------------------------------------
+/// How the next variation in a `SoundSet` is picked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    /// Random, never the same variation twice in a row
+    Random,
+    /// In order
+    RoundRobin,
+}
+
+/// What to do when a sound is played and all its voices are in use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StealPolicy {
+    /// Stop the voice that started first
+    Oldest,
+    /// Stop the voice with the lowest volume
+    Quietest,
+    /// Don't play the new sound
+    None,
+}
+
+/// Pitch and volume picked for a single play.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Variation {
+    pitch_scale: f64,
+    volume_db: f64,
+}
+
+impl Default for Variation {
+    fn default() -> Self {
+        Self {
+            pitch_scale: 1.0,
+            volume_db: 0.0,
+        }
+    }
+}
+
+struct Voice<N> {
+    node: N,
+    started: f64,
+    ends: Option<f64>,
+    volume_db: f64,
+}
+
+impl<N> Voice<N> {
+    fn has_ended(&self, time: f64) -> bool {
+        self.ends.map(|ends| time >= ends).unwrap_or(false)
+    }
+}
+
+fn is_alive(node: &ThreadBound<Ref<Node>>) -> bool {
+    if !unsafe { node.is_instance_sane() } {
+        return false;
+    }
+
+    !unsafe { node.assume_safe() }.is_queued_for_deletion()
+}
+
+fn stop_voice(node: &ThreadBound<Ref<Node>>) {
+    if unsafe { node.is_instance_sane() } {
+        unsafe { node.assume_safe() }.queue_free();
+    }
+}
+
+fn random_range((min, max): (f64, f64)) -> f64 {
+    if min >= max {
+        return min;
+    }
+    rand::thread_rng().gen_range(min, max)
+}
+
+fn random_index(len: usize) -> usize {
+    rand::thread_rng().gen_range(0, len)
+}
+
+/// Selection, cooldown and voice limit of a `SoundSet`.
+/// Time is in seconds of engine time, advanced by `advance`.
+struct Playback<N> {
+    selection: Selection,
+    max_voices: usize,
+    steal: StealPolicy,
+    cooldown: f64,
+    time: f64,
+    last: Option<usize>,
+    last_played: Option<f64>,
+    voices: Vec<Voice<N>>,
+}
 
-use crate::{gd_err, some_or_bail};
+impl<N> Playback<N> {
+    fn new() -> Self {
+        Self {
+            selection: Selection::Random,
+            max_voices: 0,
+            steal: StealPolicy::Oldest,
+            cooldown: 0.0,
+            time: 0.0,
+            last: None,
+            last_played: None,
+            voices: Vec::new(),
+        }
+    }
 
+    fn advance(&mut self, delta: f64) {
+        self.time += delta;
+    }
+
+    /// Drop voices that ended, or whose node is gone.
+    fn retain(&mut self, is_alive: impl Fn(&N) -> bool) {
+        let time = self.time;
+        self.voices
+            .retain(|voice| !voice.has_ended(time) && is_alive(&voice.node));
+    }
+
+    // Pick the variation for the next play, out of `len` variations.
+    // Returns `None` if the sound should not be played,
+    // otherwise the variation and the voice to stop, if one was stolen.
+    fn next(
+        &mut self,
+        len: usize,
+        is_alive: impl Fn(&N) -> bool,
+        random: impl FnMut(usize) -> usize,
+    ) -> Option<(usize, Option<N>)> {
+        if len == 0 {
+            return None;
+        }
+
+        if let Some(last_played) = self.last_played {
+            if self.time - last_played < self.cooldown {
+                return None;
+            }
+        }
+
+        self.retain(is_alive);
+        let stolen = if self.max_voices > 0 && self.voices.len() >= self.max_voices {
+            Some(self.voices.remove(self.steal_index()?).node)
+        } else {
+            None
+        };
+
+        let index = self.pick(len, random);
+        self.last = Some(index);
+        Some((index, stolen))
+    }
+
+    fn steal_index(&self) -> Option<usize> {
+        let voices = self.voices.iter().enumerate();
+        let by = |a: f64, b: f64| a.partial_cmp(&b).unwrap_or(Ordering::Equal);
+
+        match self.steal {
+            StealPolicy::None => None,
+            StealPolicy::Oldest => voices.min_by(|(_, a), (_, b)| by(a.started, b.started)),
+            StealPolicy::Quietest => voices.min_by(|(_, a), (_, b)| by(a.volume_db, b.volume_db)),
+        }
+        .map(|(index, _)| index)
+    }
+
+    // `random(n)` returns an index below `n`.
+    fn pick(&self, len: usize, mut random: impl FnMut(usize) -> usize) -> usize {
+        match (self.selection, self.last) {
+            (Selection::RoundRobin, Some(last)) => (last + 1) % len,
+            (Selection::RoundRobin, None) => 0,
+            (Selection::Random, Some(last)) if len > 1 => {
+                let index = random(len - 1);
+                if index >= last {
+                    index + 1
+                } else {
+                    index
+                }
+            }
+            (Selection::Random, _) => random(len),
+        }
+    }
+
+    /// Add a voice playing a stream of `length` seconds.
+    fn add(&mut self, node: N, length: f64, variation: Variation) {
+        let ends = if length > 0.0 && variation.pitch_scale > 0.0 {
+            Some(self.time + length / variation.pitch_scale)
+        } else {
+            None
+        };
+
+        self.last_played = Some(self.time);
+        self.voices.push(Voice {
+            node,
+            started: self.time,
+            ends,
+            volume_db: variation.volume_db,
+        });
+    }
+}
+
+/// A set of variations of the same sound.
+///
+/// ```ignore
+/// let gunshot = SoundSet::load(&["res://sfx/shot_1.wav", "res://sfx/shot_2.wav"])?
+///     .with_pitch(0.9, 1.1)
+///     .with_volume(-2.0, 0.0)
+///     .with_max_voices(4, StealPolicy::Oldest)
+///     .with_cooldown(0.05);
+/// ```
+pub struct SoundSet {
+    streams: Vec<Ref<AudioStream>>,
+    pitch: (f64, f64),
+    volume_db: (f64, f64),
+    bus: Option<String>,
+    playback: Playback<ThreadBound<Ref<Node>>>,
+}
+
+impl SoundSet {
+    pub fn new(streams: Vec<Ref<AudioStream>>) -> Self {
+        Self {
+            streams,
+            pitch: (1.0, 1.0),
+            volume_db: (0.0, 0.0),
+            bus: None,
+            playback: Playback::new(),
+        }
+    }
+
+    /// Load every variation.
+    /// Returns every path that failed to load.
+    pub fn load(paths: &[&str]) -> Result<Self, Vec<Error>> {
+        let mut streams = Vec::with_capacity(paths.len());
+        let mut errors = Vec::new();
+
+        for path in paths {
+            match load_resource::<AudioStream>(path) {
+                Ok(stream) => streams.push(stream),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(Self::new(streams))
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub fn with_selection(mut self, selection: Selection) -> Self {
+        self.playback.selection = selection;
+        self
+    }
+
+    /// Random pitch scale between `min` and `max`.
+    pub fn with_pitch(mut self, min: f64, max: f64) -> Self {
+        self.pitch = (min, max);
+        self
+    }
+
+    /// Random volume offset in dB between `min` and `max`.
+    pub fn with_volume(mut self, min: f64, max: f64) -> Self {
+        self.volume_db = (min, max);
+        self
+    }
+
+    /// Limit the number of voices playing at once, 0 for no limit.
+    pub fn with_max_voices(mut self, max_voices: usize, steal: StealPolicy) -> Self {
+        self.playback.max_voices = max_voices;
+        self.playback.steal = steal;
+        self
+    }
+
+    /// Minimum time in seconds between two plays.
+    pub fn with_cooldown(mut self, cooldown: f64) -> Self {
+        self.playback.cooldown = cooldown;
+        self
+    }
+
+    /// Bus used when no bus is given when playing.
+    pub fn with_bus(mut self, bus: &str) -> Self {
+        self.bus = Some(bus.to_string());
+        self
+    }
+
+    pub fn streams(&self) -> &[Ref<AudioStream>] {
+        &self.streams
+    }
+
+    /// Advance the clock used for the cooldown and voice lengths.
+    pub fn advance(&mut self, delta: f64) {
+        self.playback.advance(delta);
+    }
+
+    /// Number of voices still playing.
+    pub fn voice_count(&mut self) -> usize {
+        self.playback.retain(is_alive);
+        self.playback.voices.len()
+    }
+
+    /// Stop every playing voice.
+    pub fn stop_all(&mut self) {
+        for voice in self.playback.voices.drain(..) {
+            stop_voice(&voice.node);
+        }
+    }
+
+    // Pick the stream and variation for the next play, stealing a voice if needed.
+    // Returns `None` if the sound should not be played.
+    fn next_voice(&mut self) -> Option<(Ref<AudioStream>, Variation)> {
+        let (index, stolen) = self
+            .playback
+            .next(self.streams.len(), is_alive, random_index)?;
+
+        if let Some(node) = stolen {
+            stop_voice(&node);
+        }
+
+        let variation = Variation {
+            pitch_scale: random_range(self.pitch),
+            volume_db: random_range(self.volume_db),
+        };
+
+        Some((self.streams[index].clone(), variation))
+    }
+
+    fn add_voice(&mut self, node: Ref<Node>, stream: &Ref<AudioStream>, variation: Variation) {
+        let length = unsafe { stream.assume_safe() }.get_length();
+        self.playback.add(ThreadBound::new(node), length, variation);
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Sound bank -
+// -----------------------------------------------------------------------------
 /// Convenience storage for AudioStreams.
+/// Each key holds a `SoundSet` of one or more variations.
+///
+/// The bank is `Send` if the key is. The voices of a sound are bound to the
+/// thread that played them, and using them from another thread panics.
 pub struct SoundBank<T> {
-    inner: HashMap<T, AudioStream>,
+    inner: HashMap<T, SoundSet>,
 }
 
-unsafe impl<T> Send for SoundBank<T> {}
-unsafe impl<T> Sync for SoundBank<T> {}
-
 impl<T: Eq + Hash> SoundBank<T> {
-    pub fn insert(&mut self, key: T, path: &str) {
-        let stream = some_or_bail!(
-            load_audio_stream(path.into()),
-            "Failed to load audio stream: {}",
-            path
-        );
-        self.inner.insert(key, stream);
+    /// Insert a sound with a single variation.
+    pub fn insert(&mut self, key: T, path: &str) -> Result<()> {
+        let stream = load_resource::<AudioStream>(path)?;
+        self.inner.insert(key, SoundSet::new(vec![stream]));
+        Ok(())
+    }
+
+    pub fn insert_set(&mut self, key: T, set: SoundSet) {
+        self.inner.insert(key, set);
     }
 
     pub fn new() -> Self {
@@ -75,105 +429,825 @@ impl<T: Eq + Hash> SoundBank<T> {
         }
     }
 
-    pub fn get(&self, key: &T) -> Option<AudioStream> {
-        match self.inner.get(key) {
-            Some(s) => Some(s.clone()),
-            None => None,
+    /// Get the first variation of a sound.
+    pub fn get(&self, key: &T) -> Option<Ref<AudioStream>> {
+        self.inner
+            .get(key)
+            .and_then(|set| set.streams.first())
+            .cloned()
+    }
+
+    pub fn get_set(&self, key: &T) -> Option<&SoundSet> {
+        self.inner.get(key)
+    }
+
+    pub fn get_set_mut(&mut self, key: &T) -> Option<&mut SoundSet> {
+        self.inner.get_mut(key)
+    }
+
+    /// Play the sound on the given bus, or if `None` the bus of the sound set
+    /// or the default bus.
+    /// Returns false if there is no sound for the key, or if it was
+    /// not played due to the cooldown or voice limit.
+    pub fn play(&mut self, owner: &Node, key: &T, bus: Option<&str>) -> bool {
+        self.play_with(key, bus, |stream, variation, bus| {
+            spawn_player(owner, stream, bus, variation)
+        })
+    }
+
+    /// Play the sound at a position in 2D space.
+    /// Returns false if the sound was not played, see `play`.
+    pub fn play_at_2d(
+        &mut self,
+        owner: &Node,
+        key: &T,
+        position: Position2D,
+        settings: Positional2D,
+        bus: Option<&str>,
+    ) -> bool {
+        self.play_with(key, bus, |stream, variation, bus| {
+            spawn_player_2d(owner, stream, position, settings, bus, variation)
+        })
+    }
+
+    /// Play the sound at a position in 3D space.
+    /// Returns false if the sound was not played, see `play`.
+    pub fn play_at_3d(
+        &mut self,
+        owner: &Node,
+        key: &T,
+        position: Position3D,
+        settings: Positional3D,
+        bus: Option<&str>,
+    ) -> bool {
+        self.play_with(key, bus, |stream, variation, bus| {
+            spawn_player_3d(owner, stream, position, settings, bus, variation)
+        })
+    }
+
+    /// Advance the clock of every sound set by the frame delta.
+    /// Call this from `_process`, so cooldowns and voice lengths
+    /// follow engine time.
+    pub fn advance(&mut self, delta: f64) {
+        for set in self.inner.values_mut() {
+            set.advance(delta);
+        }
+    }
+
+    /// Stop every voice of the sound.
+    pub fn stop(&mut self, key: &T) {
+        if let Some(set) = self.inner.get_mut(key) {
+            set.stop_all();
+        }
+    }
+
+    fn play_with<F>(&mut self, key: &T, bus: Option<&str>, spawn: F) -> bool
+    where
+        F: FnOnce(Ref<AudioStream>, Variation, Option<&str>) -> Result<Ref<Node>>,
+    {
+        let set = match self.inner.get_mut(key) {
+            Some(set) => set,
+            None => return false,
+        };
+
+        let (stream, variation) = match set.next_voice() {
+            Some(voice) => voice,
+            None => return false,
+        };
+
+        let bus = bus.map(str::to_string).or_else(|| set.bus.clone());
+
+        match spawn(stream.clone(), variation, bus.as_deref()) {
+            Ok(node) => {
+                set.add_voice(node, &stream, variation);
+                true
+            }
+            Err(e) => {
+                gd_err!("Failed to play sound: {}", e);
+                false
+            }
         }
     }
 }
 
-/// Load an audio file.
-/// E.g
+impl<T: Eq + Hash> Default for SoundBank<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Definitions -
+// -----------------------------------------------------------------------------
+/// Sound banks can be defined in a config file, with one section per key.
+/// Every field except `paths` is optional.
+///
+/// ```text
+/// [Gunshot]
+/// paths=["res://sfx/shot_1.wav", "res://sfx/shot_2.wav"]
+/// bus="SFX"
+/// selection="random"        ; or "round_robin"
+/// volume=[-2.0, 0.0]        ; dB, a single value or a range
+/// pitch=[0.9, 1.1]          ; a single value or a range
+/// max_voices=4
+/// steal="oldest"            ; or "quietest", "none"
+/// cooldown=0.05
+/// ```
+///
+/// The section names are parsed with `FromStr`:
+///
 /// ```ignore
-/// load_audio_stream("res://sfx/ping.wav");
+/// let bank = SoundBank::<Sound>::from_file("res://sfx/bank.cfg")?;
 /// ```
-pub fn load_audio_stream(path: &str) -> Option<AudioStream> {
-    let mut loader = ResourceLoader::godot_singleton();
-    loader
-        .load(path.into(), "AudioStream".into(), false)?
-        .cast::<AudioStream>()
+impl<T: Eq + Hash + FromStr> SoundBank<T> {
+    /// Load a bank from a definition file.
+    /// Every error in the file is returned, including every missing audio file.
+    pub fn from_file(path: &str) -> Result<Self, Vec<Error>> {
+        let config = ConfigFile::new();
+        config.load(path.into()).map_err(|error| {
+            vec![Error::File {
+                path: path.to_string(),
+                error,
+            }]
+        })?;
+
+        let mut bank = Self::new();
+        let mut errors = Vec::new();
+
+        for section in config.get_sections().read().iter() {
+            let section = section.to_string();
+            let key = match section.parse::<T>() {
+                Ok(key) => key,
+                Err(_) => {
+                    errors.push(Error::UnknownKey(section));
+                    continue;
+                }
+            };
+
+            match read_sound_set(&config, &section) {
+                Ok(set) => bank.insert_set(key, set),
+                Err(mut e) => errors.append(&mut e),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(bank)
+        } else {
+            Err(errors)
+        }
+    }
 }
 
-/// Play an audio stream.
-/// The audio stream will free and remove it self once done playing.
-pub fn play_audio_stream(mut owner: Node, stream: AudioStream) {
-    let audio_player = Instance::<AudioPlayer>::new();
-    let player_node = *audio_player.base();
+fn read_sound_set(config: &ConfigFile, section: &str) -> Result<SoundSet, Vec<Error>> {
+    let value = |field: &str| {
+        if config.has_section_key(section.into(), field.into()) {
+            Some(config.get_value(section.into(), field.into(), Variant::new()))
+        } else {
+            None
+        }
+    };
+    let invalid = |field: &'static str| Error::InvalidValue {
+        key: section.to_string(),
+        field,
+    };
+
+    let mut errors = Vec::new();
 
-    unsafe {
-        owner.add_child(Some(player_node.to_node()), false);
+    let paths = value("paths")
+        .and_then(|v| to_strings(&v))
+        .unwrap_or_default();
+    if paths.is_empty() {
+        errors.push(invalid("paths"));
     }
 
-    let _ = audio_player
-        .map(|player, node| {
-            player.play_sound(node, stream);
-        })
-        .map_err(|e| {
-            gd_err!("Failed to play sound: {:?}", e);
-        });
+    let mut streams = Vec::with_capacity(paths.len());
+    for path in &paths {
+        match load_resource::<AudioStream>(path) {
+            Ok(stream) => streams.push(stream),
+            Err(e) => errors.push(e),
+        }
+    }
+
+    let mut set = SoundSet::new(streams);
+
+    if let Some(v) = value("bus") {
+        match v.try_to_string() {
+            Some(bus) => set = set.with_bus(&bus),
+            None => errors.push(invalid("bus")),
+        }
+    }
+
+    if let Some(v) = value("selection") {
+        match v.try_to_string().as_deref() {
+            Some("random") => set = set.with_selection(Selection::Random),
+            Some("round_robin") => set = set.with_selection(Selection::RoundRobin),
+            _ => errors.push(invalid("selection")),
+        }
+    }
+
+    if let Some(v) = value("volume") {
+        match to_range(&v) {
+            Some((min, max)) => set = set.with_volume(min, max),
+            None => errors.push(invalid("volume")),
+        }
+    }
+
+    if let Some(v) = value("pitch") {
+        match to_range(&v) {
+            Some((min, max)) => set = set.with_pitch(min, max),
+            None => errors.push(invalid("pitch")),
+        }
+    }
+
+    let steal = match value("steal").map(|v| v.try_to_string()) {
+        None => Some(StealPolicy::Oldest),
+        Some(steal) => match steal.as_deref() {
+            Some("oldest") => Some(StealPolicy::Oldest),
+            Some("quietest") => Some(StealPolicy::Quietest),
+            Some("none") => Some(StealPolicy::None),
+            _ => None,
+        },
+    };
+
+    match (value("max_voices").map(|v| v.try_to_i64()), steal) {
+        (_, None) => errors.push(invalid("steal")),
+        (None, Some(steal)) => set = set.with_max_voices(0, steal),
+        (Some(Some(max)), Some(steal)) if max >= 0 => {
+            set = set.with_max_voices(max as usize, steal)
+        }
+        (Some(_), Some(_)) => errors.push(invalid("max_voices")),
+    }
+
+    if let Some(v) = value("cooldown") {
+        match to_number(&v) {
+            Some(cooldown) => set = set.with_cooldown(cooldown),
+            None => errors.push(invalid("cooldown")),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(set)
+    } else {
+        Err(errors)
+    }
+}
+
+fn to_number(value: &Variant) -> Option<f64> {
+    value
+        .try_to_f64()
+        .or_else(|| value.try_to_i64().map(|v| v as f64))
+}
+
+// A single number, or an array of two numbers
+fn to_range(value: &Variant) -> Option<(f64, f64)> {
+    if let Some(v) = to_number(value) {
+        return Some((v, v));
+    }
+
+    let values = value
+        .try_to_array()?
+        .iter()
+        .map(|v| to_number(&v))
+        .collect::<Option<Vec<_>>>()?;
+
+    match values.as_slice() {
+        [min, max] if min <= max => Some((*min, *max)),
+        _ => None,
+    }
+}
+
+// A single string, or an array of strings
+fn to_strings(value: &Variant) -> Option<Vec<String>> {
+    if let Some(v) = value.try_to_string() {
+        return Some(vec![v]);
+    }
+
+    value
+        .try_to_array()?
+        .iter()
+        .map(|v| v.try_to_string())
+        .collect()
+}
+
+/// Play an audio stream on the given bus, or the default bus if `None`.
+/// The audio stream will free and remove it self once done playing.
+pub fn play_audio_stream(owner: &Node, stream: Ref<AudioStream>, bus: Option<&str>) -> Result<()> {
+    spawn_player(owner, stream, bus, Variation::default()).map(|_| ())
+}
+
+fn spawn_player(
+    owner: &Node,
+    stream: Ref<AudioStream>,
+    bus: Option<&str>,
+    variation: Variation,
+) -> Result<Ref<Node>> {
+    let audio_player = Instance::<AudioPlayer, Unique>::new().into_shared();
+    let audio_player = unsafe { audio_player.assume_safe() };
+
+    owner.add_child(audio_player.base(), false);
+
+    play_instance(audio_player, stream, bus, variation)
 }
 
 // -----------------------------------------------------------------------------
-//     - Audio player -
+//     - Positional audio -
 // -----------------------------------------------------------------------------
-/// Audio player node.
-/// Attach this script to an audio stream player, and it can be set to loop 
-#[derive(NativeClass)]
-#[inherit(AudioStreamPlayer)]
-pub struct AudioPlayer {
-    #[property(path = "base/Loop")]
-    should_loop: bool,
-}
-
-#[methods]
-impl AudioPlayer {
-    pub fn _init(_owner: AudioStreamPlayer) -> Self {
-        Self { should_loop: false }
-    }
-
-    fn connect_signals(&self, mut owner: AudioStreamPlayer) {
-        unsafe {
-            let obj = &owner.to_object();
-            let res = owner.connect(
-                "finished".into(),
-                Some(*obj),
-                "on_sound_finished".into(),
-                VariantArray::new(),
-                0,
-            );
-
-            if let Err(e) = res {
-                godot_print!("failed to connect audio signal: {:?}", e);
-            }
+/// Where a 2D sound is played.
+pub enum Position2D<'a> {
+    /// A fixed global position
+    At(Vector2),
+    /// Follow a node, the player is added as a child of the node.
+    /// The sound stops if the node is freed.
+    Follow(&'a Node2D),
+}
+
+/// Where a 3D sound is played.
+pub enum Position3D<'a> {
+    /// A fixed global position
+    At(Vector3),
+    /// Follow a node, the player is added as a child of the node.
+    /// The sound stops if the node is freed.
+    Follow(&'a Spatial),
+}
+
+/// Distance settings for `AudioStreamPlayer2D`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Positional2D {
+    /// Attenuation curve exponent
+    pub attenuation: f64,
+    /// Distance in pixels beyond which the sound is silent
+    pub max_distance: f64,
+}
+
+impl Default for Positional2D {
+    fn default() -> Self {
+        Self {
+            attenuation: 1.0,
+            max_distance: 2000.0,
+        }
+    }
+}
+
+/// Distance settings for `AudioStreamPlayer3D`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Positional3D {
+    /// One of the `AudioStreamPlayer3D::ATTENUATION_*` constants
+    pub attenuation_model: i64,
+    /// Scale of the attenuation curve
+    pub unit_size: f64,
+    /// Distance beyond which the sound is silent, 0.0 for no limit
+    pub max_distance: f64,
+}
+
+impl Default for Positional3D {
+    fn default() -> Self {
+        Self {
+            attenuation_model: AudioStreamPlayer3D::ATTENUATION_INVERSE_DISTANCE,
+            unit_size: 1.0,
+            max_distance: 0.0,
         }
     }
+}
+
+/// Play an audio stream at a position in 2D space,
+/// on the given bus or the default bus if `None`.
+/// The audio stream will free and remove it self once done playing.
+pub fn play_audio_stream_2d(
+    owner: &Node,
+    stream: Ref<AudioStream>,
+    position: Position2D,
+    settings: Positional2D,
+    bus: Option<&str>,
+) -> Result<()> {
+    spawn_player_2d(owner, stream, position, settings, bus, Variation::default()).map(|_| ())
+}
+
+fn spawn_player_2d(
+    owner: &Node,
+    stream: Ref<AudioStream>,
+    position: Position2D,
+    settings: Positional2D,
+    bus: Option<&str>,
+    variation: Variation,
+) -> Result<Ref<Node>> {
+    let audio_player = Instance::<AudioPlayer2D, Unique>::new().into_shared();
+    let audio_player = unsafe { audio_player.assume_safe() };
+    let node = audio_player.base();
 
-    #[export]
-    pub fn _ready(&mut self, mut owner: AudioStreamPlayer) {
-        unsafe {
-            owner.stop();
+    match position {
+        Position2D::At(position) => {
+            owner.add_child(node, false);
+            node.set_global_position(position);
         }
-        self.connect_signals(owner);
+        Position2D::Follow(target) => target.add_child(node, false),
     }
 
-    fn play_sound(&self, mut owner: AudioStreamPlayer, audio_stream: AudioStream) {
-        unsafe {
-            owner.set_stream(Some(audio_stream));
-            owner.play(0.0);
+    node.set_attenuation(settings.attenuation);
+    node.set_max_distance(settings.max_distance);
+
+    play_instance(audio_player, stream, bus, variation)
+}
+
+/// Play an audio stream at a position in 3D space,
+/// on the given bus or the default bus if `None`.
+/// The audio stream will free and remove it self once done playing.
+pub fn play_audio_stream_3d(
+    owner: &Node,
+    stream: Ref<AudioStream>,
+    position: Position3D,
+    settings: Positional3D,
+    bus: Option<&str>,
+) -> Result<()> {
+    spawn_player_3d(owner, stream, position, settings, bus, Variation::default()).map(|_| ())
+}
+
+fn spawn_player_3d(
+    owner: &Node,
+    stream: Ref<AudioStream>,
+    position: Position3D,
+    settings: Positional3D,
+    bus: Option<&str>,
+    variation: Variation,
+) -> Result<Ref<Node>> {
+    let audio_player = Instance::<AudioPlayer3D, Unique>::new().into_shared();
+    let audio_player = unsafe { audio_player.assume_safe() };
+    let node = audio_player.base();
+
+    match position {
+        Position3D::At(position) => {
+            owner.add_child(node, false);
+            let mut transform = node.get_global_transform();
+            transform.origin = position;
+            node.set_global_transform(transform);
         }
+        Position3D::Follow(target) => target.add_child(node, false),
     }
 
-    #[export]
-    pub fn on_sound_finished(&self, mut owner: AudioStreamPlayer) {
-        unsafe {
-            if self.should_loop {
-                // Play again
-                owner.play(0.0);
-            } else {
+    node.set_attenuation_model(settings.attenuation_model);
+    node.set_unit_size(settings.unit_size);
+    node.set_max_distance(settings.max_distance);
+
+    play_instance(audio_player, stream, bus, variation)
+}
+
+/// Create a pool of audio players for `play_audio_stream_pooled`.
+pub fn audio_player_pool(preload: usize, capacity: usize) -> NodePool<AudioStreamPlayer> {
+    let released = ReleaseQueue::new();
+    let queue = released.clone();
+
+    NodePool::from_factory(
+        move || {
+            let player = Instance::emplace(AudioPlayer {
+                should_loop: false,
+                release: Some(queue.clone()),
+            });
+            Some(player.into_base().into_shared())
+        },
+        preload,
+        capacity,
+    )
+    .with_release_queue(released)
+}
+
+/// Play an audio stream using a player from the pool,
+/// on the given bus or the default bus if `None`.
+/// Once done playing the player is released back into the pool.
+/// Returns `Error::PoolExhausted` if every player is in use.
+pub fn play_audio_stream_pooled(
+    owner: &Node,
+    pool: &mut NodePool<AudioStreamPlayer>,
+    stream: Ref<AudioStream>,
+    bus: Option<&str>,
+) -> Result<()> {
+    let player = pool.acquire(owner).ok_or(Error::PoolExhausted)?;
+    let player = unsafe { player.assume_safe() };
+    player.set_bus(bus.unwrap_or(DEFAULT_BUS).into());
+    player.set_stream(stream);
+    player.play(0.0);
+    Ok(())
+}
+
+// -----------------------------------------------------------------------------
+//     - Audio player -
+// -----------------------------------------------------------------------------
+trait PlaySound: NativeClass {
+    fn play_sound(
+        &self,
+        owner: TRef<Self::Base>,
+        audio_stream: Ref<AudioStream>,
+        bus: Option<&str>,
+        variation: Variation,
+    );
+}
+
+// Start playing on a player that was just added to the tree.
+fn play_instance<T>(
+    audio_player: RefInstance<T, Shared>,
+    stream: Ref<AudioStream>,
+    bus: Option<&str>,
+    variation: Variation,
+) -> Result<Ref<Node>>
+where
+    T: PlaySound,
+    T::Base: SubClass<Node>,
+    T::UserData: Map,
+{
+    let node = audio_player.base().upcast::<Node>().claim();
+
+    audio_player
+        .map(|player, base| player.play_sound(base, stream, bus, variation))
+        .map_err(|_| Error::Borrow(T::class_name().to_string()))
+        .map(|_| node)
+}
+
+// The players only differ in the base node, so they are generated from
+// the same definition.
+macro_rules! audio_player {
+    ($(#[$meta:meta])* $name: ident, $base: ident, $set_volume: ident) => {
+        $(#[$meta])*
+        #[derive(NativeClass)]
+        #[inherit($base)]
+        pub struct $name {
+            #[property(path = "base/Loop")]
+            should_loop: bool,
+            release: Option<ReleaseQueue>,
+        }
+
+        #[methods]
+        impl $name {
+            fn new(_owner: &$base) -> Self {
+                Self {
+                    should_loop: false,
+                    release: None,
+                }
+            }
+
+            fn connect_signals(&self, owner: &$base) {
+                let _ = owner
+                    .connect(
+                        "finished".into(),
+                        owner,
+                        "on_sound_finished".into(),
+                        VariantArray::new_shared(),
+                        0,
+                    )
+                    .map_err(|error| Error::Signal {
+                        signal: "finished".to_string(),
+                        error,
+                    })
+                    .log_err();
+            }
+
+            #[export]
+            fn _ready(&mut self, owner: &$base) {
                 owner.stop();
-                owner.queue_free();
+                self.connect_signals(owner);
+            }
+
+            #[export]
+            fn on_sound_finished(&self, owner: &$base) {
+                if self.should_loop {
+                    // Play again
+                    owner.play(0.0);
+                } else if let Some(release) = &self.release {
+                    owner.stop();
+                    release.release(owner.upcast());
+                } else {
+                    owner.stop();
+                    owner.queue_free();
+                }
+            }
+        }
+
+        impl PlaySound for $name {
+            fn play_sound(
+                &self,
+                owner: TRef<$base>,
+                audio_stream: Ref<AudioStream>,
+                bus: Option<&str>,
+                variation: Variation,
+            ) {
+                owner.set_bus(bus.unwrap_or(DEFAULT_BUS).into());
+                owner.set_stream(audio_stream);
+                owner.set_pitch_scale(variation.pitch_scale);
+                owner.$set_volume(variation.volume_db);
+                owner.play(0.0);
             }
         }
+    };
+}
+
+audio_player!(
+    /// Audio player node.
+    /// Attach this script to an audio stream player, and it can be set to loop.
+    /// A player created by `audio_player_pool` is released back into the pool
+    /// once done, instead of freeing itself.
+    AudioPlayer,
+    AudioStreamPlayer,
+    set_volume_db
+);
+
+audio_player!(
+    /// Positional audio player node for 2D, see `AudioPlayer`.
+    AudioPlayer2D,
+    AudioStreamPlayer2D,
+    set_volume_db
+);
+
+audio_player!(
+    /// Positional audio player node for 3D, see `AudioPlayer`.
+    AudioPlayer3D,
+    AudioStreamPlayer3D,
+    set_unit_db
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    enum Sound {
+        Jump,
+        Hit,
+    }
+
+    #[test]
+    fn insert_set_and_lookup() {
+        let mut bank = SoundBank::new();
+        bank.insert_set(Sound::Jump, SoundSet::new(Vec::new()).with_bus("SFX"));
+
+        let set = bank.get_set(&Sound::Jump).expect("missing sound set");
+        assert_eq!(set.bus.as_deref(), Some("SFX"));
+        assert!(set.streams().is_empty());
+        assert!(bank.get_set(&Sound::Hit).is_none());
+
+        // No variations, so there is no first variation either
+        assert!(bank.get(&Sound::Jump).is_none());
+        assert!(bank.get(&Sound::Hit).is_none());
+    }
+
+    #[test]
+    fn insert_set_replaces_existing_key() {
+        let mut bank = SoundBank::new();
+        bank.insert_set(Sound::Jump, SoundSet::new(Vec::new()).with_bus("SFX"));
+        bank.insert_set(Sound::Jump, SoundSet::new(Vec::new()).with_bus("UI"));
+
+        let set = bank.get_set_mut(&Sound::Jump).expect("missing sound set");
+        assert_eq!(set.bus.as_deref(), Some("UI"));
+        assert_eq!(set.voice_count(), 0);
+    }
+
+    #[test]
+    fn play_without_sound_does_not_spawn() {
+        let mut bank = SoundBank::new();
+        bank.insert_set(Sound::Jump, SoundSet::new(Vec::new()));
+
+        let spawn = |_: Ref<AudioStream>, _: Variation, _: Option<&str>| -> Result<Ref<Node>> {
+            panic!("nothing should be spawned")
+        };
+        assert!(!bank.play_with(&Sound::Hit, None, spawn));
+        assert!(!bank.play_with(&Sound::Jump, None, spawn));
+    }
+
+    fn alive(_: &u32) -> bool {
+        true
+    }
+
+    fn first(_: usize) -> usize {
+        0
+    }
+
+    fn last(len: usize) -> usize {
+        len - 1
+    }
+
+    fn variation(volume_db: f64) -> Variation {
+        Variation {
+            pitch_scale: 1.0,
+            volume_db,
+        }
+    }
+
+    fn picks(playback: &mut Playback<u32>, random: fn(usize) -> usize, count: usize) -> Vec<usize> {
+        (0..count)
+            .map(|_| playback.next(3, alive, random).expect("not played").0)
+            .collect()
+    }
+
+    #[test]
+    fn round_robin_wraps() {
+        let mut playback = Playback::<u32>::new();
+        playback.selection = Selection::RoundRobin;
+        assert_eq!(picks(&mut playback, first, 5), vec![0, 1, 2, 0, 1]);
+    }
+
+    #[test]
+    fn random_never_repeats() {
+        let mut playback = Playback::<u32>::new();
+        assert_eq!(picks(&mut playback, first, 4), vec![0, 1, 0, 1]);
+
+        let mut playback = Playback::<u32>::new();
+        assert_eq!(picks(&mut playback, last, 4), vec![2, 1, 2, 1]);
+    }
+
+    #[test]
+    fn random_single_variation_repeats() {
+        let mut playback = Playback::<u32>::new();
+        assert_eq!(playback.next(1, alive, first), Some((0, None)));
+        assert_eq!(playback.next(1, alive, first), Some((0, None)));
+    }
+
+    #[test]
+    fn no_variations_is_not_played() {
+        let mut playback = Playback::<u32>::new();
+        assert_eq!(playback.next(0, alive, first), None);
+    }
+
+    #[test]
+    fn cooldown_follows_advance() {
+        let mut playback = Playback::new();
+        playback.cooldown = 0.1;
+
+        assert!(playback.next(3, alive, first).is_some());
+        playback.add(1, 0.0, variation(0.0));
+        assert!(playback.next(3, alive, first).is_none());
+
+        playback.advance(0.06);
+        assert!(playback.next(3, alive, first).is_none());
+
+        playback.advance(0.06);
+        assert!(playback.next(3, alive, first).is_some());
+    }
+
+    #[test]
+    fn voices_end_after_their_length() {
+        let mut playback = Playback::new();
+        playback.add(
+            1,
+            1.0,
+            Variation {
+                pitch_scale: 2.0,
+                volume_db: 0.0,
+            },
+        );
+        playback.add(2, 0.0, variation(0.0));
+
+        playback.advance(0.4);
+        playback.retain(alive);
+        assert_eq!(playback.voices.len(), 2);
+
+        // Streams without a length play until their node is gone
+        playback.advance(0.2);
+        playback.retain(alive);
+        assert_eq!(playback.voices.len(), 1);
+
+        playback.retain(|node| *node != 2);
+        assert!(playback.voices.is_empty());
+    }
+
+    fn full_playback(steal: StealPolicy) -> Playback<u32> {
+        let mut playback = Playback::new();
+        playback.max_voices = 3;
+        playback.steal = steal;
+
+        playback.add(1, 0.0, variation(-3.0));
+        playback.advance(0.1);
+        playback.add(2, 0.0, variation(-6.0));
+        playback.advance(0.1);
+        playback.add(3, 0.0, variation(0.0));
+        playback
+    }
+
+    #[test]
+    fn steal_oldest_voice() {
+        let mut playback = full_playback(StealPolicy::Oldest);
+        assert_eq!(playback.next(3, alive, first), Some((0, Some(1))));
+        assert_eq!(playback.voices.len(), 2);
+    }
+
+    #[test]
+    fn steal_oldest_voice_in_the_same_frame() {
+        let mut playback = Playback::new();
+        playback.max_voices = 2;
+        playback.add(1, 0.0, variation(0.0));
+        playback.add(2, 0.0, variation(0.0));
+
+        assert_eq!(playback.next(3, alive, first), Some((0, Some(1))));
+    }
+
+    #[test]
+    fn steal_quietest_voice() {
+        let mut playback = full_playback(StealPolicy::Quietest);
+        assert_eq!(playback.next(3, alive, first), Some((0, Some(2))));
+    }
+
+    #[test]
+    fn no_steal_skips_the_play() {
+        let mut playback = full_playback(StealPolicy::None);
+        assert_eq!(playback.next(3, alive, first), None);
+        assert_eq!(playback.voices.len(), 3);
+
+        // A voice that is gone frees a slot
+        assert_eq!(playback.next(3, |node| *node != 2, first), Some((0, None)));
     }
 }
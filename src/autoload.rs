@@ -0,0 +1,89 @@
+//! Access to autoloaded (singleton) scripts.
+//!
+//! An autoload is a node added under `/root` by Godot, e.g. `/root/SceneLoader`.
+//!
+//! ```ignore
+//! let loader = autoload::<SceneLoader>(owner, "SceneLoader")?;
+//! ```
+//!
+//! Singleton style classes can implement `Autoload` with `impl_autoload!`
+//! next to their `#[derive(NativeClass)]`, so the name is declared once:
+//!
+//! ```ignore
+//! #[derive(NativeClass)]
+//! #[inherit(Node)]
+//! pub struct GameState {
+//!     score: u32,
+//! }
+//!
+//! impl_autoload!(GameState, "GameState");
+//!
+//! // Elsewhere
+//! GameState::with_autoload(owner, |state, _node| state.score += 1)?;
+//! ```
+use gdnative::api::Node;
+use gdnative::{
+    GodotObject, Instance, ManuallyManaged, MapMut, NativeClass, Shared, SubClass, TRef,
+};
+
+use crate::error::{Error, Result};
+
+// -----------------------------------------------------------------------------
+//     - Autoload -
+// -----------------------------------------------------------------------------
+/// Get the script instance of the autoload `name`.
+pub fn autoload<T>(owner: &Node, name: &str) -> Result<Instance<T, Shared>>
+where
+    T: NativeClass,
+    T::Base: GodotObject<RefKind = ManuallyManaged> + SubClass<Node>,
+{
+    let path = format!("/root/{}", name);
+    let node = owner
+        .get_node(path.as_str().into())
+        .ok_or_else(|| Error::NodeNotFound(path.clone()))?;
+    let node = unsafe { node.assume_safe() };
+
+    let base = node.cast::<T::Base>().ok_or_else(|| Error::Cast {
+        path: path.clone(),
+        expected: T::Base::class_name(),
+    })?;
+
+    base.cast_instance::<T>()
+        .map(|instance| instance.claim())
+        .ok_or(Error::MissingScript(path))
+}
+
+/// A script that is added as an autoload under a fixed name.
+/// Implement with `impl_autoload!`.
+pub trait Autoload: NativeClass
+where
+    Self::Base: GodotObject<RefKind = ManuallyManaged> + SubClass<Node>,
+{
+    /// Name of the autoload node under `/root`
+    const NAME: &'static str;
+
+    fn autoload(owner: &Node) -> Result<Instance<Self, Shared>> {
+        autoload::<Self>(owner, Self::NAME)
+    }
+
+    /// Borrow the autoload script mutably.
+    fn with_autoload<F, R>(owner: &Node, f: F) -> Result<R>
+    where
+        Self::UserData: MapMut,
+        F: FnOnce(&mut Self, TRef<Self::Base>) -> R,
+    {
+        let instance = Self::autoload(owner)?;
+        unsafe { instance.assume_safe() }
+            .map_mut(f)
+            .map_err(|_| Error::Borrow(format!("/root/{}", Self::NAME)))
+    }
+}
+
+#[macro_export]
+macro_rules! impl_autoload {
+    ($type: ty, $name: expr) => {
+        impl $crate::autoload::Autoload for $type {
+            const NAME: &'static str = $name;
+        }
+    };
+}
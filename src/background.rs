@@ -0,0 +1,455 @@
+//! Background resource loading
+//! Load batches of resources on worker threads.
+//!
+//! ```ignore
+//! let mut loader = BackgroundLoader::new(2)?;
+//! let handle = loader.load_batch(&["res://World.tscn", "res://music.ogg"], Priority::High);
+//!
+//! // In `_process`
+//! if let Some(results) = handle.try_results() {
+//!     let scene = results.get::<PackedScene>("res://World.tscn")?;
+//! } else {
+//!     progress_bar.set_value(handle.progress() as f64);
+//! }
+//! ```
+//!
+//! A `LoadHandle` is also a `Future`, and can be awaited by an executor.
+use gdnative::api::Resource;
+use gdnative::{GodotObject, Ref, RefCounted, SubClass};
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll, Waker};
+
+use crate::error::{Error, Result};
+use crate::resource::load_untyped;
+
+// -----------------------------------------------------------------------------
+//     - Priority -
+// -----------------------------------------------------------------------------
+/// Jobs with a higher priority are picked up first.
+/// Jobs with the same priority are loaded in the order they were queued.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Low,
+    Normal,
+    High,
+}
+
+struct Job {
+    priority: Priority,
+    seq: u64,
+    path: String,
+    batch: Arc<Batch>,
+}
+
+impl PartialEq for Job {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority && self.seq == other.seq
+    }
+}
+
+impl Eq for Job {}
+
+impl PartialOrd for Job {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Job {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Batch -
+// -----------------------------------------------------------------------------
+struct Batch {
+    total: usize,
+    completed: AtomicUsize,
+    cancelled: AtomicBool,
+    results: Mutex<Option<LoadResults>>,
+    waker: Mutex<Option<Waker>>,
+}
+
+impl Batch {
+    fn new(total: usize) -> Self {
+        Self {
+            total,
+            completed: AtomicUsize::new(0),
+            cancelled: AtomicBool::new(false),
+            results: Mutex::new(Some(LoadResults::default())),
+            waker: Mutex::new(None),
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.completed.load(AtomicOrdering::Acquire) >= self.total
+    }
+
+    fn complete(&self, path: String, result: Option<Result<Ref<Resource>>>) {
+        {
+            let mut results = self.results.lock().expect("load results poisoned");
+            let results = results.get_or_insert_with(LoadResults::default);
+            match result {
+                Some(Ok(resource)) => {
+                    results.resources.insert(path, resource);
+                }
+                Some(Err(e)) => results.errors.push(e),
+                None => (),
+            }
+        }
+
+        self.completed.fetch_add(1, AtomicOrdering::AcqRel);
+
+        if self.is_done() {
+            if let Some(waker) = self.waker.lock().expect("waker poisoned").take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// Resources loaded by a batch.
+#[derive(Default)]
+pub struct LoadResults {
+    resources: HashMap<String, Ref<Resource>>,
+    errors: Vec<Error>,
+}
+
+impl LoadResults {
+    /// Get a loaded resource and cast it to `T`.
+    pub fn get<T>(&self, path: &str) -> Result<Ref<T>>
+    where
+        T: GodotObject<RefKind = RefCounted> + SubClass<Resource>,
+    {
+        self.resources
+            .get(path)
+            .ok_or_else(|| Error::Load(path.to_string()))?
+            .clone()
+            .cast::<T>()
+            .ok_or_else(|| Error::Cast {
+                path: path.to_string(),
+                expected: T::class_name(),
+            })
+    }
+
+    pub fn resources(&self) -> impl Iterator<Item = (&str, &Ref<Resource>)> {
+        self.resources.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// Every resource that failed to load.
+    pub fn errors(&self) -> &[Error] {
+        &self.errors
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Handle -
+// -----------------------------------------------------------------------------
+/// Handle to a batch of resources being loaded.
+pub struct LoadHandle {
+    batch: Arc<Batch>,
+}
+
+impl LoadHandle {
+    /// Number of resources done loading (or skipped, if cancelled).
+    pub fn completed(&self) -> usize {
+        self.batch.completed.load(AtomicOrdering::Acquire)
+    }
+
+    pub fn total(&self) -> usize {
+        self.batch.total
+    }
+
+    /// Progress between 0.0 and 1.0
+    pub fn progress(&self) -> f32 {
+        if self.batch.total == 0 {
+            return 1.0;
+        }
+        self.completed() as f32 / self.batch.total as f32
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.batch.is_done()
+    }
+
+    /// Skip all resources that have not started loading yet.
+    /// Resources already being loaded are still completed.
+    pub fn cancel(&self) {
+        self.batch.cancelled.store(true, AtomicOrdering::Release);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.batch.cancelled.load(AtomicOrdering::Acquire)
+    }
+
+    /// Take the results once the batch is done.
+    /// Returns `None` while loading, or if the results were already taken.
+    pub fn try_results(&self) -> Option<LoadResults> {
+        if !self.is_done() {
+            return None;
+        }
+
+        self.batch
+            .results
+            .lock()
+            .expect("load results poisoned")
+            .take()
+    }
+}
+
+impl Future for LoadHandle {
+    type Output = LoadResults;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        if !self.is_done() {
+            *self.batch.waker.lock().expect("waker poisoned") = Some(cx.waker().clone());
+
+            // The batch could have finished before the waker was stored
+            if !self.is_done() {
+                return Poll::Pending;
+            }
+        }
+
+        let results = self
+            .batch
+            .results
+            .lock()
+            .expect("load results poisoned")
+            .take();
+        Poll::Ready(results.unwrap_or_default())
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Loader -
+// -----------------------------------------------------------------------------
+/// Loads resources on a dedicated rayon thread pool.
+pub struct BackgroundLoader {
+    pool: ThreadPool,
+    queue: Arc<Mutex<BinaryHeap<Job>>>,
+    batches: Vec<Weak<Batch>>,
+    seq: u64,
+}
+
+impl BackgroundLoader {
+    pub fn new(threads: usize) -> Result<Self> {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("gdextras-loader-{}", i))
+            .build()?;
+
+        Ok(Self {
+            pool,
+            queue: Arc::new(Mutex::new(BinaryHeap::new())),
+            batches: Vec::new(),
+            seq: 0,
+        })
+    }
+
+    /// Queue a batch of resources.
+    pub fn load_batch(&mut self, paths: &[&str], priority: Priority) -> LoadHandle {
+        let batch = Arc::new(Batch::new(paths.len()));
+
+        self.batches.retain(|b| b.strong_count() > 0);
+        self.batches.push(Arc::downgrade(&batch));
+
+        for path in paths {
+            self.seq += 1;
+            let job = Job {
+                priority,
+                seq: self.seq,
+                path: path.to_string(),
+                batch: batch.clone(),
+            };
+            self.queue.lock().expect("load queue poisoned").push(job);
+
+            // Every spawned task runs whichever job has the highest priority
+            let queue = self.queue.clone();
+            self.pool.spawn(move || {
+                let job = queue.lock().expect("load queue poisoned").pop();
+                if let Some(job) = job {
+                    run_job(job, |path| load_untyped(path, ""));
+                }
+            });
+        }
+
+        LoadHandle { batch }
+    }
+
+    /// Aggregate progress of all unfinished batches as `(completed, total)`.
+    pub fn progress(&self) -> (usize, usize) {
+        progress(&self.batches)
+    }
+
+    /// Cancel every queued batch.
+    pub fn cancel_all(&self) {
+        for batch in self.batches.iter().filter_map(Weak::upgrade) {
+            batch.cancelled.store(true, AtomicOrdering::Release);
+        }
+    }
+}
+
+fn progress(batches: &[Weak<Batch>]) -> (usize, usize) {
+    batches
+        .iter()
+        .filter_map(Weak::upgrade)
+        .filter(|b| !b.is_done())
+        .fold((0, 0), |(completed, total), b| {
+            (
+                completed + b.completed.load(AtomicOrdering::Acquire),
+                total + b.total,
+            )
+        })
+}
+
+fn run_job(job: Job, load: impl FnOnce(&str) -> Result<Ref<Resource>>) {
+    if job.batch.cancelled.load(AtomicOrdering::Acquire) {
+        job.batch.complete(job.path, None);
+        return;
+    }
+
+    let result = load(&job.path);
+    job.batch.complete(job.path, Some(result));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::task::Wake;
+
+    fn job(priority: Priority, seq: u64, batch: &Arc<Batch>) -> Job {
+        Job {
+            priority,
+            seq,
+            path: format!("res://{}.tres", seq),
+            batch: batch.clone(),
+        }
+    }
+
+    fn fail(path: &str) -> Result<Ref<Resource>> {
+        Err(Error::Load(path.to_string()))
+    }
+
+    #[derive(Default)]
+    struct CountWakes(AtomicUsize);
+
+    impl Wake for CountWakes {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, AtomicOrdering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn jobs_are_ordered_by_priority_then_seq() {
+        let batch = Arc::new(Batch::new(4));
+        let mut queue = BinaryHeap::new();
+        queue.push(job(Priority::Low, 1, &batch));
+        queue.push(job(Priority::High, 3, &batch));
+        queue.push(job(Priority::Normal, 2, &batch));
+        queue.push(job(Priority::High, 4, &batch));
+
+        let order = std::iter::from_fn(|| queue.pop().map(|j| j.seq)).collect::<Vec<_>>();
+        assert_eq!(order, vec![3, 4, 2, 1]);
+    }
+
+    #[test]
+    fn handle_progress() {
+        let handle = LoadHandle {
+            batch: Arc::new(Batch::new(2)),
+        };
+        assert_eq!(handle.progress(), 0.0);
+
+        run_job(job(Priority::Normal, 1, &handle.batch), fail);
+        assert_eq!(handle.progress(), 0.5);
+        assert!(!handle.is_done());
+        assert!(handle.try_results().is_none());
+
+        run_job(job(Priority::Normal, 2, &handle.batch), fail);
+        assert_eq!(handle.progress(), 1.0);
+        assert_eq!(handle.try_results().unwrap().errors().len(), 2);
+        assert!(handle.try_results().is_none());
+    }
+
+    #[test]
+    fn empty_batch_is_done() {
+        let handle = LoadHandle {
+            batch: Arc::new(Batch::new(0)),
+        };
+        assert_eq!(handle.progress(), 1.0);
+        assert!(handle.is_done());
+    }
+
+    #[test]
+    fn loader_progress_skips_finished_batches() {
+        let a = Arc::new(Batch::new(2));
+        let b = Arc::new(Batch::new(1));
+        let dropped = Arc::downgrade(&Arc::new(Batch::new(5)));
+        let batches = vec![Arc::downgrade(&a), Arc::downgrade(&b), dropped];
+
+        run_job(job(Priority::Normal, 1, &a), fail);
+        assert_eq!(progress(&batches), (1, 3));
+
+        run_job(job(Priority::Normal, 2, &b), fail);
+        assert_eq!(progress(&batches), (1, 2));
+    }
+
+    #[test]
+    fn cancel_before_pickup_skips_loading() {
+        let handle = LoadHandle {
+            batch: Arc::new(Batch::new(1)),
+        };
+        handle.cancel();
+
+        run_job(job(Priority::Normal, 1, &handle.batch), |_| {
+            panic!("cancelled job was loaded")
+        });
+        assert!(handle.is_done());
+        assert!(handle.try_results().unwrap().errors().is_empty());
+    }
+
+    #[test]
+    fn cancel_after_pickup_completes_the_job() {
+        let handle = LoadHandle {
+            batch: Arc::new(Batch::new(1)),
+        };
+
+        run_job(job(Priority::Normal, 1, &handle.batch), |path| {
+            handle.cancel();
+            fail(path)
+        });
+        assert!(handle.is_cancelled());
+        assert!(handle.is_done());
+        assert_eq!(handle.try_results().unwrap().errors().len(), 1);
+    }
+
+    #[test]
+    fn future_is_woken_when_done() {
+        let mut handle = LoadHandle {
+            batch: Arc::new(Batch::new(1)),
+        };
+        let wakes = Arc::new(CountWakes::default());
+        let waker = Waker::from(wakes.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        assert!(Pin::new(&mut handle).poll(&mut cx).is_pending());
+        assert_eq!(wakes.0.load(AtomicOrdering::SeqCst), 0);
+
+        run_job(job(Priority::Normal, 1, &handle.batch), fail);
+        assert_eq!(wakes.0.load(AtomicOrdering::SeqCst), 1);
+
+        match Pin::new(&mut handle).poll(&mut cx) {
+            Poll::Ready(results) => assert_eq!(results.errors().len(), 1),
+            Poll::Pending => panic!("done batch is pending"),
+        }
+    }
+}
@@ -0,0 +1,204 @@
+//! Rust closures invoked by Godot signals and timers.
+//!
+//! This requires the `Callback` class to be registered:
+//!
+//! ```ignore
+//! fn init(handle: init::InitHandle) {
+//!     handle.add_class::<gdextras::callback::Callback>();
+//! }
+//! ```
+use gdnative::api::{Node, Object, Timer};
+use gdnative::{methods, Instance, NativeClass, Ref, Variant, VariantArray};
+
+use crate::error::{Error, Result};
+
+type CallbackFn<A> = Box<dyn FnMut(A) + Send>;
+
+/// Most signal arguments `invoke` accepts.
+/// Godot fails to call methods with fewer parameters than the signal has arguments.
+pub const MAX_SIGNAL_ARGS: usize = 6;
+
+// A closure, dropped after the first call if oneshot
+struct Slot<A> {
+    func: Option<CallbackFn<A>>,
+    oneshot: bool,
+}
+
+impl<A> Slot<A> {
+    fn new(func: CallbackFn<A>, oneshot: bool) -> Self {
+        Self {
+            func: Some(func),
+            oneshot,
+        }
+    }
+
+    /// Call the closure, returns true once it will not be called again.
+    fn call(&mut self, arg: A) -> bool {
+        if let Some(func) = self.func.as_mut() {
+            func(arg);
+        }
+
+        if self.oneshot {
+            self.func = None;
+        }
+        self.func.is_none()
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Callback node -
+// -----------------------------------------------------------------------------
+/// Node holding a closure.
+/// Connect a signal to `invoke` to run the closure.
+/// A oneshot callback frees itself after the first call.
+#[derive(NativeClass)]
+#[inherit(Node)]
+pub struct Callback {
+    slot: Option<Slot<Variant>>,
+}
+
+#[methods]
+impl Callback {
+    fn new(_owner: &Node) -> Self {
+        Self { slot: None }
+    }
+
+    /// Run the closure with the first argument.
+    /// Accepts signals with up to `MAX_SIGNAL_ARGS` arguments.
+    #[export]
+    #[allow(clippy::too_many_arguments)]
+    pub fn invoke(
+        &mut self,
+        owner: &Node,
+        #[opt] arg: Variant,
+        #[opt] _arg2: Variant,
+        #[opt] _arg3: Variant,
+        #[opt] _arg4: Variant,
+        #[opt] _arg5: Variant,
+        #[opt] _arg6: Variant,
+    ) {
+        let done = match self.slot.as_mut() {
+            Some(slot) => slot.call(arg),
+            None => return,
+        };
+
+        if done {
+            self.slot = None;
+            owner.queue_free();
+        }
+    }
+}
+
+/// Connect `signal` on `source` to a closure.
+/// The callback node is added as a child of `parent`, and is freed with it.
+/// The closure receives the first argument of the signal, or `Nil`.
+/// Signals with more than `MAX_SIGNAL_ARGS` arguments are not supported.
+pub fn connect_callback<F>(
+    source: &Object,
+    signal: &str,
+    parent: &Node,
+    oneshot: bool,
+    func: F,
+) -> Result<Ref<Node>>
+where
+    F: FnMut(Variant) + Send + 'static,
+{
+    let callback = Instance::emplace(Callback {
+        slot: Some(Slot::new(Box::new(func), oneshot)),
+    });
+    let node = callback.into_base().into_shared();
+
+    unsafe {
+        parent.add_child(node.assume_safe(), false);
+    }
+
+    let flags = if oneshot { Object::CONNECT_ONESHOT } else { 0 };
+    source
+        .connect(
+            signal.into(),
+            node.clone(),
+            "invoke".into(),
+            VariantArray::new_shared(),
+            flags,
+        )
+        .map_err(|error| Error::Signal {
+            signal: signal.to_string(),
+            error,
+        })?;
+
+    Ok(node)
+}
+
+// -----------------------------------------------------------------------------
+//     - Timers -
+// -----------------------------------------------------------------------------
+/// Call `func` once after `seconds`.
+/// A one shot `Timer` is added as a child of `parent` and removed once done.
+/// Freeing `parent` before the timer runs out cancels the call.
+pub fn call_after<F>(parent: &Node, seconds: f64, func: F) -> Result<()>
+where
+    F: FnOnce() + Send + 'static,
+{
+    let timer = Timer::new();
+    timer.set_wait_time(seconds);
+    timer.set_one_shot(true);
+    timer.set_autostart(true);
+    let timer = timer.into_shared();
+
+    unsafe {
+        parent.add_child(timer.assume_safe(), false);
+    }
+
+    let timer_node = unsafe { timer.assume_safe() };
+    let mut func = Some(func);
+    let result = connect_callback(
+        timer_node.upcast(),
+        "timeout",
+        timer_node.upcast(),
+        true,
+        move |_| {
+            if let Some(func) = func.take() {
+                func();
+            }
+            unsafe { timer.assume_safe() }.queue_free();
+        },
+    );
+
+    if result.is_err() {
+        timer_node.queue_free();
+    }
+
+    result.map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn recording_slot(oneshot: bool) -> (Slot<i32>, Arc<Mutex<Vec<i32>>>) {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let recorded = calls.clone();
+        let slot = Slot::new(
+            Box::new(move |arg| recorded.lock().unwrap().push(arg)),
+            oneshot,
+        );
+        (slot, calls)
+    }
+
+    #[test]
+    fn slot_passes_argument() {
+        let (mut slot, calls) = recording_slot(false);
+        assert!(!slot.call(1));
+        assert!(!slot.call(2));
+        assert_eq!(*calls.lock().unwrap(), [1, 2]);
+    }
+
+    #[test]
+    fn oneshot_slot_is_called_once() {
+        let (mut slot, calls) = recording_slot(true);
+        assert!(slot.call(1));
+        assert!(slot.call(2));
+        assert_eq!(*calls.lock().unwrap(), [1]);
+    }
+}
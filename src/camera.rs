@@ -0,0 +1,616 @@
+//! Camera control for `Camera2D`.
+//!
+//! The controller moves the camera itself, so the built-in smoothing
+//! and drag margins of the camera should be disabled.
+//!
+//! ```ignore
+//! // Camera script
+//! fn _ready(&mut self, owner: &Camera2D) {
+//!     let player = owner.get_and_cast::<Node2D>("../Player");
+//!     let tilemap = owner.get_and_cast::<TileMap>("../TileMap");
+//!
+//!     self.controller = CameraController2D::new()
+//!         .with_deadzone(Vector2::new(64.0, 32.0))
+//!         .with_look_ahead(0.3, 80.0);
+//!     self.controller.add_target(player);
+//!     self.controller.set_limits_from_tilemap(tilemap);
+//!     self.controller.snap(owner);
+//! }
+//!
+//! fn _process(&mut self, owner: &Camera2D, delta: f64) {
+//!     self.controller.update(owner, delta);
+//! }
+//!
+//! // When the player is hit
+//! controller.add_trauma(0.5);
+//! ```
+use gdnative::api::{Camera2D, Node2D, TileMap};
+use gdnative::{Ref, Vector2};
+
+use crate::tween::{Animate, Ease, Tween};
+
+// -----------------------------------------------------------------------------
+//     - Shake -
+// -----------------------------------------------------------------------------
+/// Trauma based screen shake.
+///
+/// Trauma goes from 0 to 1 and decays over time. The shake is the square
+/// of the trauma, so small hits give a small shake and large hits a large one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shake {
+    trauma: f32,
+    decay: f32,
+    max_offset: Vector2,
+    max_roll: f32,
+    frequency: f32,
+    time: f32,
+    seed: u32,
+}
+
+impl Default for Shake {
+    fn default() -> Self {
+        Self::new(Vector2::new(16.0, 12.0), 0.0)
+    }
+}
+
+impl Shake {
+    /// `max_offset` in pixels, and `max_roll` in radians.
+    /// Roll requires `rotating` to be enabled on the camera.
+    pub fn new(max_offset: Vector2, max_roll: f32) -> Self {
+        Self {
+            trauma: 0.0,
+            decay: 1.0,
+            max_offset,
+            max_roll,
+            frequency: 15.0,
+            time: 0.0,
+            seed: rand::random(),
+        }
+    }
+
+    /// Trauma removed per second.
+    pub fn with_decay(mut self, decay: f32) -> Self {
+        self.decay = decay;
+        self
+    }
+
+    /// How fast the camera moves while shaking.
+    pub fn with_frequency(mut self, frequency: f32) -> Self {
+        self.frequency = frequency;
+        self
+    }
+
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
+    }
+
+    pub fn trauma(&self) -> f32 {
+        self.trauma
+    }
+
+    /// Advance the shake, and get the offset and roll.
+    pub fn update(&mut self, delta: f64) -> (Vector2, f32) {
+        self.time += delta as f32;
+        self.trauma = (self.trauma - self.decay * delta as f32).max(0.0);
+
+        let amount = self.trauma * self.trauma;
+        if amount <= 0.0 {
+            return (Vector2::zero(), 0.0);
+        }
+
+        let t = self.time * self.frequency;
+        let offset = Vector2::new(
+            self.max_offset.x * amount * perlin(self.seed, t),
+            self.max_offset.y * amount * perlin(self.seed.wrapping_add(1), t),
+        );
+        let roll = self.max_roll * amount * perlin(self.seed.wrapping_add(2), t);
+        (offset, roll)
+    }
+}
+
+// 1D Perlin noise between -1 and 1
+fn perlin(seed: u32, x: f32) -> f32 {
+    let x0 = x.floor();
+    let t = x - x0;
+    let i = x0 as i32;
+
+    let d0 = gradient(seed, i) * t;
+    let d1 = gradient(seed, i.wrapping_add(1)) * (t - 1.0);
+    let fade = t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+
+    (d0 + (d1 - d0) * fade) * 2.0
+}
+
+// Pseudo random gradient between -1 and 1 for a lattice point
+fn gradient(seed: u32, i: i32) -> f32 {
+    let mut h = seed ^ (i as u32).wrapping_mul(0x9e37_79b9);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85eb_ca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2_ae35);
+    h ^= h >> 16;
+    (h as f32 / u32::MAX as f32) * 2.0 - 1.0
+}
+
+// -----------------------------------------------------------------------------
+//     - Camera controller -
+// -----------------------------------------------------------------------------
+struct Target {
+    id: i64,
+    node: Ref<Node2D>,
+}
+
+/// Follows one or more targets with a `Camera2D`.
+///
+/// With more than one target the camera frames all of them,
+/// zooming out as far as `max_zoom`.
+pub struct CameraController2D {
+    targets: Vec<Target>,
+    follow_speed: f32,
+    deadzone: Vector2,
+    look_ahead: f32,
+    max_look_ahead: f32,
+    look_ahead_speed: f32,
+    frame_margin: Vector2,
+    max_zoom: f32,
+    zoom: f32,
+    zoom_tween: Option<Tween<f32>>,
+    limits: Option<(Vector2, Vector2)>,
+    shake: Shake,
+
+    focus: Option<Vector2>,
+    last_target: Option<Vector2>,
+    look_offset: Vector2,
+    frame_zoom: f32,
+}
+
+impl Default for CameraController2D {
+    fn default() -> Self {
+        Self {
+            targets: Vec::new(),
+            follow_speed: 5.0,
+            deadzone: Vector2::zero(),
+            look_ahead: 0.0,
+            max_look_ahead: 0.0,
+            look_ahead_speed: 3.0,
+            frame_margin: Vector2::new(64.0, 64.0),
+            max_zoom: 2.0,
+            zoom: 1.0,
+            zoom_tween: None,
+            limits: None,
+            shake: Shake::default(),
+            focus: None,
+            last_target: None,
+            look_offset: Vector2::zero(),
+            frame_zoom: 1.0,
+        }
+    }
+}
+
+impl CameraController2D {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How fast the camera catches up with the target.
+    /// Zero follows the target without smoothing.
+    pub fn with_follow_speed(mut self, speed: f32) -> Self {
+        self.follow_speed = speed;
+        self
+    }
+
+    /// Size of the area around the center of the screen
+    /// where the target can move without moving the camera.
+    pub fn with_deadzone(mut self, size: Vector2) -> Self {
+        self.deadzone = size;
+        self
+    }
+
+    /// Look ahead of the target, by the distance it moves in `seconds`,
+    /// up to `max_distance`.
+    pub fn with_look_ahead(mut self, seconds: f32, max_distance: f32) -> Self {
+        self.look_ahead = seconds;
+        self.max_look_ahead = max_distance;
+        self
+    }
+
+    /// How fast the look ahead follows changes in velocity.
+    pub fn with_look_ahead_speed(mut self, speed: f32) -> Self {
+        self.look_ahead_speed = speed;
+        self
+    }
+
+    /// Space kept around the targets when framing more than one,
+    /// and how far the camera can zoom out to frame them.
+    pub fn with_framing(mut self, margin: Vector2, max_zoom: f32) -> Self {
+        self.frame_margin = margin;
+        self.max_zoom = max_zoom;
+        self
+    }
+
+    pub fn with_shake(mut self, shake: Shake) -> Self {
+        self.shake = shake;
+        self
+    }
+
+    pub fn with_zoom(mut self, zoom: f32) -> Self {
+        self.zoom = zoom;
+        self.frame_zoom = zoom;
+        self
+    }
+
+    // -------------------------------------------------------------------------
+    //     - Targets -
+    // -------------------------------------------------------------------------
+    pub fn add_target(&mut self, target: &Node2D) {
+        let id = target.get_instance_id();
+        if self.targets.iter().all(|t| t.id != id) {
+            self.targets.push(Target {
+                id,
+                node: unsafe { target.assume_shared() },
+            });
+            self.last_target = None;
+        }
+    }
+
+    pub fn remove_target(&mut self, target: &Node2D) {
+        let id = target.get_instance_id();
+        self.targets.retain(|t| t.id != id);
+        self.last_target = None;
+    }
+
+    /// Replace all targets with a single target.
+    pub fn set_target(&mut self, target: &Node2D) {
+        self.clear_targets();
+        self.add_target(target);
+    }
+
+    /// The camera stays where it is without targets.
+    pub fn clear_targets(&mut self) {
+        self.targets.clear();
+        self.last_target = None;
+    }
+
+    // -------------------------------------------------------------------------
+    //     - Zoom -
+    // -------------------------------------------------------------------------
+    /// A zoom of 2.0 shows twice as much as 1.0.
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom_tween = None;
+        self.zoom = zoom;
+    }
+
+    /// Tween the zoom over `duration` seconds.
+    pub fn zoom_to(&mut self, zoom: f32, duration: f64, ease: Ease) {
+        self.zoom_tween = Some(Tween::new(self.zoom, zoom, duration).with_ease(ease));
+    }
+
+    // -------------------------------------------------------------------------
+    //     - Limits -
+    // -------------------------------------------------------------------------
+    /// Keep the view inside the rectangle from `min` to `max`, in global coordinates.
+    /// Areas smaller than the view are centered.
+    pub fn set_limits(&mut self, min: Vector2, max: Vector2) {
+        self.limits = Some((min, max));
+    }
+
+    /// Keep the view inside the used cells of a tilemap.
+    pub fn set_limits_from_tilemap(&mut self, tilemap: &TileMap) {
+        let used = tilemap.get_used_rect();
+        let start = Vector2::new(used.origin.x, used.origin.y);
+        let end = start + Vector2::new(used.size.width, used.size.height);
+
+        let start = tilemap.to_global(tilemap.map_to_world(start, false));
+        let end = tilemap.to_global(tilemap.map_to_world(end, false));
+        self.set_limits(start.min(end), start.max(end));
+    }
+
+    pub fn clear_limits(&mut self) {
+        self.limits = None;
+    }
+
+    // -------------------------------------------------------------------------
+    //     - Shake -
+    // -------------------------------------------------------------------------
+    /// Shake the camera, see `Shake`.
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.shake.add_trauma(amount);
+    }
+
+    pub fn shake(&self) -> &Shake {
+        &self.shake
+    }
+
+    pub fn shake_mut(&mut self) -> &mut Shake {
+        &mut self.shake
+    }
+
+    // -------------------------------------------------------------------------
+    //     - Update -
+    // -------------------------------------------------------------------------
+    /// Move the camera to the targets without smoothing,
+    /// e.g. when a level starts.
+    pub fn snap(&mut self, camera: &Camera2D) {
+        self.focus = None;
+        self.last_target = None;
+        self.look_offset = Vector2::zero();
+        self.frame_zoom = self.zoom;
+        self.update(camera, 0.0);
+    }
+
+    pub fn update(&mut self, camera: &Camera2D, delta: f64) {
+        if let Some(tween) = self.zoom_tween.as_mut() {
+            tween.update(delta);
+            self.zoom = tween.value();
+            if tween.is_finished() {
+                self.zoom_tween = None;
+            }
+        }
+
+        self.targets
+            .retain(|t| unsafe { t.node.is_instance_sane() });
+        let positions = self
+            .targets
+            .iter()
+            .map(|t| unsafe { t.node.assume_safe() }.get_global_position())
+            .collect::<Vec<_>>();
+
+        let view = camera.get_viewport_rect().size;
+        let view = Vector2::new(view.width, view.height);
+        let snap = self.focus.is_none();
+
+        // Frame all targets
+        let (target, zoom) = match bounds(&positions) {
+            Some((min, max)) if positions.len() > 1 => {
+                let size = max - min + self.frame_margin * 2.0;
+                let fit = (size.x / view.x).max(size.y / view.y).min(self.max_zoom);
+                ((min + max) / 2.0, self.zoom.max(fit))
+            }
+            Some((min, _)) => (min, self.zoom),
+            None => (
+                self.focus.unwrap_or_else(|| camera.get_global_position()),
+                self.zoom,
+            ),
+        };
+
+        let follow = smoothing(self.follow_speed, delta);
+        self.frame_zoom = if snap {
+            zoom
+        } else {
+            self.frame_zoom + (zoom - self.frame_zoom) * follow
+        };
+
+        // Look ahead
+        let velocity = match self.last_target {
+            Some(last) if delta > 0.0 => (target - last) / delta as f32,
+            _ => Vector2::zero(),
+        };
+        self.last_target = Some(target);
+
+        let mut look_ahead = velocity * self.look_ahead;
+        if look_ahead.length() > self.max_look_ahead {
+            look_ahead = look_ahead.normalize() * self.max_look_ahead;
+        }
+        self.look_offset +=
+            (look_ahead - self.look_offset) * smoothing(self.look_ahead_speed, delta);
+
+        // Follow
+        let focus = self.focus.unwrap_or(target);
+        let desired = deadzone(focus, target, self.deadzone / 2.0);
+        let focus = if snap {
+            desired
+        } else {
+            focus + (desired - focus) * follow
+        };
+        self.focus = Some(focus);
+
+        let half_view = view * self.frame_zoom / 2.0;
+        let center = match self.limits {
+            Some((min, max)) => clamp_view(focus + self.look_offset, half_view, min, max),
+            None => focus + self.look_offset,
+        };
+
+        camera.set_global_position(center);
+        camera.set_zoom(Vector2::new(self.frame_zoom, self.frame_zoom));
+
+        let (offset, roll) = self.shake.update(delta);
+        camera.set_offset(offset);
+        if self.shake.max_roll != 0.0 {
+            camera.set_rotation(roll as f64);
+        }
+    }
+}
+
+// Fraction to move towards a target this frame, independent of frame rate
+fn smoothing(speed: f32, delta: f64) -> f32 {
+    if speed > 0.0 {
+        1.0 - (-speed * delta as f32).exp()
+    } else {
+        1.0
+    }
+}
+
+// Move `focus` just enough to keep `target` inside the deadzone
+fn deadzone(focus: Vector2, target: Vector2, half_size: Vector2) -> Vector2 {
+    let axis = |focus: f32, target: f32, half: f32| {
+        if target > focus + half {
+            target - half
+        } else if target < focus - half {
+            target + half
+        } else {
+            focus
+        }
+    };
+
+    Vector2::new(
+        axis(focus.x, target.x, half_size.x),
+        axis(focus.y, target.y, half_size.y),
+    )
+}
+
+fn clamp_view(center: Vector2, half_view: Vector2, min: Vector2, max: Vector2) -> Vector2 {
+    let axis = |center: f32, half: f32, min: f32, max: f32| {
+        if max - min <= half * 2.0 {
+            (min + max) / 2.0
+        } else {
+            center.max(min + half).min(max - half)
+        }
+    };
+
+    Vector2::new(
+        axis(center.x, half_view.x, min.x, max.x),
+        axis(center.y, half_view.y, min.y, max.y),
+    )
+}
+
+fn bounds(positions: &[Vector2]) -> Option<(Vector2, Vector2)> {
+    let first = *positions.first()?;
+    Some(
+        positions
+            .iter()
+            .fold((first, first), |(min, max), p| (min.min(*p), max.max(*p))),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: Vector2, b: Vector2) {
+        assert!((a - b).length() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn perlin_stays_in_range() {
+        for seed in 0..64 {
+            for i in 0..2000 {
+                let x = i as f32 * 0.037 - 20.0;
+                let value = perlin(seed, x);
+                assert!(
+                    (-1.0..=1.0).contains(&value),
+                    "perlin({}, {}) = {}",
+                    seed,
+                    x,
+                    value
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn perlin_is_zero_at_lattice_points() {
+        for i in -5..5 {
+            assert_eq!(perlin(7, i as f32), 0.0);
+        }
+    }
+
+    #[test]
+    fn gradient_stays_in_range() {
+        for i in -1000..1000 {
+            let g = gradient(42, i);
+            assert!((-1.0..=1.0).contains(&g));
+        }
+    }
+
+    #[test]
+    fn shake_trauma_is_clamped_and_decays() {
+        let mut shake = Shake::new(Vector2::new(10.0, 10.0), 0.1).with_decay(0.5);
+        shake.add_trauma(2.0);
+        assert_eq!(shake.trauma(), 1.0);
+
+        let (offset, roll) = shake.update(1.0);
+        assert!((shake.trauma() - 0.5).abs() < 1e-6);
+        assert!(offset.x.abs() <= 10.0 && offset.y.abs() <= 10.0);
+        assert!(roll.abs() <= 0.1);
+
+        shake.update(1.0);
+        assert_eq!(shake.trauma(), 0.0);
+        assert_eq!(shake.update(0.1), (Vector2::zero(), 0.0));
+    }
+
+    #[test]
+    fn smoothing_is_frame_rate_independent() {
+        assert_eq!(smoothing(0.0, 0.1), 1.0);
+
+        let full = smoothing(5.0, 0.2);
+        let half = smoothing(5.0, 0.1);
+        assert!(full > 0.0 && full < 1.0);
+        assert!((1.0 - (1.0 - half) * (1.0 - half) - full).abs() < 1e-6);
+    }
+
+    #[test]
+    fn deadzone_keeps_focus_inside() {
+        let focus = Vector2::new(0.0, 0.0);
+        let half = Vector2::new(10.0, 5.0);
+        assert_near(deadzone(focus, Vector2::new(3.0, -2.0), half), focus);
+        assert_near(
+            deadzone(focus, Vector2::new(14.0, -8.0), half),
+            Vector2::new(4.0, -3.0),
+        );
+    }
+
+    #[test]
+    fn deadzone_exact_edge_does_not_move() {
+        let focus = Vector2::new(0.0, 0.0);
+        let half = Vector2::new(10.0, 5.0);
+        assert_near(deadzone(focus, Vector2::new(10.0, 5.0), half), focus);
+        assert_near(deadzone(focus, Vector2::new(-10.0, -5.0), half), focus);
+    }
+
+    #[test]
+    fn clamp_view_inside_limits() {
+        let min = Vector2::new(0.0, 0.0);
+        let max = Vector2::new(1000.0, 400.0);
+        let half = Vector2::new(50.0, 50.0);
+        assert_near(
+            clamp_view(Vector2::new(500.0, 200.0), half, min, max),
+            Vector2::new(500.0, 200.0),
+        );
+        assert_near(
+            clamp_view(Vector2::new(10.0, 390.0), half, min, max),
+            Vector2::new(50.0, 350.0),
+        );
+    }
+
+    #[test]
+    fn clamp_view_larger_than_limits_is_centered() {
+        let min = Vector2::new(0.0, 0.0);
+        let max = Vector2::new(300.0, 400.0);
+        assert_near(
+            clamp_view(
+                Vector2::new(20.0, 20.0),
+                Vector2::new(200.0, 50.0),
+                min,
+                max,
+            ),
+            Vector2::new(150.0, 50.0),
+        );
+        // Exactly as large as the limits
+        assert_near(
+            clamp_view(
+                Vector2::new(20.0, 20.0),
+                Vector2::new(150.0, 200.0),
+                min,
+                max,
+            ),
+            Vector2::new(150.0, 200.0),
+        );
+    }
+
+    #[test]
+    fn bounds_of_positions() {
+        assert_eq!(bounds(&[]), None);
+        assert_eq!(
+            bounds(&[
+                Vector2::new(1.0, 5.0),
+                Vector2::new(-3.0, 2.0),
+                Vector2::new(4.0, -1.0),
+            ]),
+            Some((Vector2::new(-3.0, -1.0), Vector2::new(4.0, 5.0)))
+        );
+    }
+}
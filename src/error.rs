@@ -0,0 +1,178 @@
+//! Errors
+//!
+//! Every fallible function in the crate returns `gdextras::Error`,
+//! so errors from different modules can be propagated with `?`:
+//!
+//! ```ignore
+//! fn spawn_enemy(owner: &Node) -> gdextras::Result<()> {
+//!     let enemy = owner.spawn_scene::<Node2D>("res://Enemy.tscn", None)?;
+//!     let texture = load_resource::<Texture>("res://enemy.png")?;
+//!     GameState::with_autoload(owner, |state, _| state.enemies += 1)?;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! `GdResultExt` logs errors to the Godot output, so failures in e.g.
+//! `_process` or signal handlers can be handled without nesting matches:
+//!
+//! ```ignore
+//! #[export]
+//! fn on_body_entered(&mut self, owner: &Area2D, body: Ref<Node>) {
+//!     let score = self.score_label(owner).log_err();
+//!     let lives = self.lives(owner).or_log(0);
+//! }
+//! ```
+use gdnative::{godot_error, GodotError, VariantType};
+use log::SetLoggerError;
+use rayon::ThreadPoolBuildError;
+use std::fmt::{self, Debug};
+use std::panic::Location;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// A Godot call failed
+    Godot(GodotError),
+    /// The resource does not exist or could not be loaded
+    Load(String),
+    /// The node or resource is not of the expected type
+    Cast {
+        path: String,
+        expected: &'static str,
+    },
+    /// No node at the given path
+    NodeNotFound(String),
+    /// The node is not in the tree and has no parent
+    NoParent,
+    /// The packed scene could not be instanced
+    Instance(String),
+    /// Failed to connect a signal
+    Signal { signal: String, error: GodotError },
+    /// The node does not have the expected script attached
+    MissingScript(String),
+    /// The script is already borrowed
+    Borrow(String),
+    /// Failed to read or write a file
+    File { path: String, error: GodotError },
+    /// No audio bus with the given name
+    BusNotFound(String),
+    /// No effect at the given index on the audio bus
+    EffectNotFound { bus: String, index: i64 },
+    /// A section name in a definition file that does not parse as a key
+    UnknownKey(String),
+    /// A missing or malformed field in a definition file
+    InvalidValue { key: String, field: &'static str },
+    /// No animation with the given name
+    AnimationNotFound(String),
+    /// No animation tree parameter with the given name
+    ParameterNotFound(String),
+    /// The value is not of the type of the animation tree parameter
+    ParameterType { name: String, expected: VariantType },
+    /// The thread pool could not be created
+    ThreadPool(String),
+    /// The logger could not be installed, or the log file could not be opened
+    Logger(String),
+    /// A scene change was requested while changing to the given scene
+    SceneChangeInProgress(String),
+    /// The main thread was not recorded with `thread::init_main`
+    NoMainThread,
+    /// Every node of the pool is in use
+    PoolExhausted,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Godot(e) => write!(f, "godot error: {:?}", e),
+            Error::Load(path) => write!(f, "failed to load resource: {}", path),
+            Error::Cast { path, expected } => write!(f, "{} is not of type {}", path, expected),
+            Error::NodeNotFound(path) => write!(f, "node not found: {}", path),
+            Error::NoParent => write!(f, "node has no parent"),
+            Error::Instance(path) => write!(f, "failed to instance scene: {}", path),
+            Error::Signal { signal, error } => {
+                write!(f, "failed to connect signal \"{}\": {:?}", signal, error)
+            }
+            Error::MissingScript(path) => {
+                write!(f, "node does not have the expected script: {}", path)
+            }
+            Error::Borrow(path) => write!(f, "script is already borrowed: {}", path),
+            Error::File { path, error } => write!(f, "failed to access {}: {:?}", path, error),
+            Error::BusNotFound(bus) => write!(f, "audio bus not found: {}", bus),
+            Error::EffectNotFound { bus, index } => {
+                write!(f, "no effect at index {} on audio bus {}", index, bus)
+            }
+            Error::UnknownKey(key) => write!(f, "unknown key: {}", key),
+            Error::InvalidValue { key, field } => {
+                write!(f, "invalid value for {} in {}", field, key)
+            }
+            Error::AnimationNotFound(name) => write!(f, "animation not found: {}", name),
+            Error::ParameterNotFound(name) => write!(f, "parameter not found: {}", name),
+            Error::ParameterType { name, expected } => {
+                write!(
+                    f,
+                    "parameter {} expects a value of type {:?}",
+                    name, expected
+                )
+            }
+            Error::ThreadPool(e) => write!(f, "failed to create thread pool: {}", e),
+            Error::Logger(e) => write!(f, "logger error: {}", e),
+            Error::SceneChangeInProgress(path) => {
+                write!(f, "already changing scene to {}", path)
+            }
+            Error::NoMainThread => write!(f, "main thread not recorded, call thread::init_main"),
+            Error::PoolExhausted => write!(f, "node pool exhausted"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<GodotError> for Error {
+    fn from(e: GodotError) -> Self {
+        Error::Godot(e)
+    }
+}
+
+impl From<ThreadPoolBuildError> for Error {
+    fn from(e: ThreadPoolBuildError) -> Self {
+        Error::ThreadPool(e.to_string())
+    }
+}
+
+impl From<SetLoggerError> for Error {
+    fn from(e: SetLoggerError) -> Self {
+        Error::Logger(e.to_string())
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Logging -
+// -----------------------------------------------------------------------------
+
+pub trait GdResultExt<T> {
+    /// Log the error, and convert the result to an `Option`.
+    fn log_err(self) -> Option<T>;
+
+    /// Log the error, and return `default` instead.
+    fn or_log(self, default: T) -> T;
+}
+
+impl<T, E: Debug> GdResultExt<T> for Result<T, E> {
+    #[track_caller]
+    fn log_err(self) -> Option<T> {
+        match self {
+            Ok(val) => Some(val),
+            Err(e) => {
+                let location = Location::caller();
+                godot_error!("{}:{} {:?}", location.file(), location.line(), e);
+                None
+            }
+        }
+    }
+
+    #[track_caller]
+    fn or_log(self, default: T) -> T {
+        self.log_err().unwrap_or(default)
+    }
+}
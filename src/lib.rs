@@ -1,10 +1,27 @@
 pub mod animation;
-// pub mod audio;
+pub mod audio;
+pub mod autoload;
+pub mod background;
+pub mod callback;
+pub mod camera;
+pub mod error;
 pub mod input;
+pub mod logger;
+pub mod mixer;
 pub mod mouse;
 pub mod movement;
+pub mod music;
 pub mod node_ext;
-// pub mod scene_loader;
+pub mod panic;
+pub mod pool;
+pub mod resource;
+pub mod scene_loader;
+pub mod state_machine;
+pub mod thread;
+pub mod transition;
+pub mod tween;
+
+pub use error::{Error, Result};
 
 #[macro_export]
 macro_rules! gd_unimplemented {
@@ -39,13 +56,36 @@ macro_rules! gdp {
 #[macro_export]
 macro_rules! gd_panic {
     ($($arg:tt)*) => ({
-        $crate::gd_err!($($arg)*);
+        // An installed panic hook reports the message itself
+        if !$crate::panic::is_hook_installed() {
+            $crate::gd_err!($($arg)*);
+        }
         panic!($($arg)*);
     });
 }
 
+/// Unwrap an `Option`, or log an error and return.
+///
+/// ```ignore
+/// let node = some_or_bail!(owner.get_node("Sprite".into()), "missing sprite");
+///
+/// // Return a value, in functions that don't return `()`
+/// let node = some_or_bail!(owner.get_node("Sprite".into()) => false, "missing sprite");
+/// ```
 #[macro_export]
 macro_rules! some_or_bail {
+    ($opt:expr => $default:expr, $($arg:tt)*) => ({
+        match $opt {
+            Some(val) => val,
+            None => {
+                let line = std::line!();
+                let file = std::file!();
+                let val: String = format!($($arg)*);
+                gdnative::godot_error!("{}:{} {}", file, line, val);
+                return $default
+            }
+        }
+    });
     ($opt:expr, $($arg:tt)*) => ({
         match $opt {
             Some(val) => val,
@@ -59,3 +99,39 @@ macro_rules! some_or_bail {
         }
     });
 }
+
+/// Unwrap a `Result`, or log the error and return.
+///
+/// ```ignore
+/// ok_or_bail!(owner.connect(...), "failed to connect signal");
+///
+/// // Return a value, in functions that don't return `()`
+/// let value = ok_or_bail!(config.load(path.into()) => None, "failed to load {}", path);
+/// ```
+#[macro_export]
+macro_rules! ok_or_bail {
+    ($res:expr => $default:expr, $($arg:tt)*) => ({
+        match $res {
+            Ok(val) => val,
+            Err(e) => {
+                let line = std::line!();
+                let file = std::file!();
+                let val: String = format!($($arg)*);
+                gdnative::godot_error!("{}:{} {}: {:?}", file, line, val, e);
+                return $default
+            }
+        }
+    });
+    ($res:expr, $($arg:tt)*) => ({
+        match $res {
+            Ok(val) => val,
+            Err(e) => {
+                let line = std::line!();
+                let file = std::file!();
+                let val: String = format!($($arg)*);
+                gdnative::godot_error!("{}:{} {}: {:?}", file, line, val, e);
+                return
+            }
+        }
+    });
+}
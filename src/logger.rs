@@ -0,0 +1,359 @@
+//! Logger for the `log` crate, writing to the Godot output.
+//!
+//! * `Error` is written with `godot_error!`
+//! * `Warn` is written with `godot_warn!`
+//! * `Info`, `Debug` and `Trace` are written with `godot_print!`
+//!
+//! ```ignore
+//! // lib.rs
+//! fn init(handle: init::InitHandle) {
+//!     gdextras::logger::init(LevelFilter::Info).ok();
+//!     gdextras::logger::set_module_level("my_game::ai", LevelFilter::Trace);
+//!     gdextras::logger::set_log_file("user://logs/game.log", 1024 * 1024, 3).ok();
+//! }
+//!
+//! // Anywhere, including worker threads
+//! log::warn!("enemy {} has no path", id);
+//! ```
+//!
+//! Levels can be changed at any time, e.g. from a debug console.
+use gdnative::api::ProjectSettings;
+use gdnative::{godot_error, godot_print, godot_warn};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::{Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::{Error, Result};
+
+static LOGGER: GodotLogger = GodotLogger {
+    filter: RwLock::new(Filter {
+        level: LevelFilter::Info,
+        modules: Vec::new(),
+    }),
+    file: Mutex::new(None),
+};
+
+/// Install the logger with a default level.
+/// Fails if a logger is already installed.
+pub fn init(level: LevelFilter) -> Result<()> {
+    log::set_logger(&LOGGER)?;
+    set_level(level);
+    Ok(())
+}
+
+/// Set the level for modules without a module level.
+pub fn set_level(level: LevelFilter) {
+    let mut filter = LOGGER.filter.write().expect("log filter poisoned");
+    filter.level = level;
+    log::set_max_level(filter.max_level());
+}
+
+/// Set the level for a module and its submodules, e.g. `my_game::ai`.
+pub fn set_module_level(module: &str, level: LevelFilter) {
+    let mut filter = LOGGER.filter.write().expect("log filter poisoned");
+    match filter.modules.iter_mut().find(|(m, _)| m == module) {
+        Some((_, l)) => *l = level,
+        None => filter.modules.push((module.to_string(), level)),
+    }
+    log::set_max_level(filter.max_level());
+}
+
+/// Remove the level of a module, so it uses the default level.
+pub fn reset_module_level(module: &str) {
+    let mut filter = LOGGER.filter.write().expect("log filter poisoned");
+    filter.modules.retain(|(m, _)| m != module);
+    log::set_max_level(filter.max_level());
+}
+
+/// Also write log messages to a file.
+/// `path` can be a Godot path such as `user://logs/game.log`.
+///
+/// Once the file is larger than `max_size` bytes it is rotated:
+/// `game.log` is renamed `game.log.1`, `game.log.1` is renamed `game.log.2`,
+/// and so on, keeping at most `max_files` old files.
+pub fn set_log_file(path: &str, max_size: u64, max_files: usize) -> Result<()> {
+    let path = PathBuf::from(
+        ProjectSettings::godot_singleton()
+            .globalize_path(path.into())
+            .to_string(),
+    );
+
+    let file = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| LogFile::open(path.clone(), max_size, max_files))
+        .map_err(|e| Error::Logger(format!("{}: {}", path.display(), e)))?;
+    *LOGGER.file.lock().expect("log file poisoned") = Some(file);
+    Ok(())
+}
+
+/// Stop writing log messages to a file.
+pub fn close_log_file() {
+    let file = LOGGER.file.lock().expect("log file poisoned").take();
+    if let Some(mut file) = file {
+        let _ = file.file.flush();
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Filter -
+// -----------------------------------------------------------------------------
+struct Filter {
+    level: LevelFilter,
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl Filter {
+    // The most specific module level wins
+    fn level(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .filter(|(module, _)| {
+                target == module
+                    || (target.starts_with(module.as_str())
+                        && target[module.len()..].starts_with("::"))
+            })
+            .max_by_key(|(module, _)| module.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(self.level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.level, |max, level| max.max(level))
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Log file -
+// -----------------------------------------------------------------------------
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    max_files: usize,
+}
+
+impl LogFile {
+    fn open(path: PathBuf, max_size: u64, max_files: usize) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+
+        Ok(Self {
+            path,
+            file,
+            size,
+            max_size,
+            max_files,
+        })
+    }
+
+    fn write(&mut self, line: &str) -> io::Result<()> {
+        // Lines larger than max_size are written to an empty file,
+        // instead of rotating on every write
+        let len = line.len() as u64;
+        if self.max_size > 0 && self.size > 0 && self.size + len > self.max_size {
+            self.rotate()?;
+        }
+
+        self.file.write_all(line.as_bytes())?;
+        self.size += len;
+        Ok(())
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        path.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        if self.max_files == 0 {
+            self.file = File::create(&self.path)?;
+            self.size = 0;
+            return Ok(());
+        }
+
+        let _ = fs::remove_file(self.rotated_path(self.max_files));
+        for index in (1..self.max_files).rev() {
+            let from = self.rotated_path(index);
+            if from.exists() {
+                fs::rename(&from, self.rotated_path(index + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.rotated_path(1))?;
+
+        self.file = File::create(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Logger -
+// -----------------------------------------------------------------------------
+struct GodotLogger {
+    filter: RwLock<Filter>,
+    file: Mutex<Option<LogFile>>,
+}
+
+impl Log for GodotLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        match self.filter.read() {
+            Ok(filter) => metadata.level() <= filter.level(metadata.target()),
+            Err(_) => false,
+        }
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let location = match (record.file(), record.line()) {
+            (Some(file), Some(line)) => format!(" {}:{}", file, line),
+            _ => String::new(),
+        };
+
+        let message = format!("[{}]{} {}", record.target(), location, record.args());
+
+        match record.level() {
+            Level::Error => godot_error!("{}", message),
+            Level::Warn => godot_warn!("{}", message),
+            level => godot_print!("{:<5} {}", level, message),
+        }
+
+        if let Ok(mut file) = self.file.lock() {
+            if let Some(file) = file.as_mut() {
+                let line = format!("{} {:<5} {}\n", timestamp(), record.level(), message);
+                if let Err(e) = file.write(&line) {
+                    godot_error!("failed to write log file: {}", e);
+                }
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            if let Some(file) = file.as_mut() {
+                let _ = file.file.flush();
+            }
+        }
+    }
+}
+
+// Seconds since the unix epoch, with milliseconds
+fn timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    format!("{}.{:03}", now.as_secs(), now.subsec_millis())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(modules: &[(&str, LevelFilter)]) -> Filter {
+        Filter {
+            level: LevelFilter::Warn,
+            modules: modules
+                .iter()
+                .map(|(module, level)| (module.to_string(), *level))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn filter_uses_default_level() {
+        let filter = filter(&[("game::ai", LevelFilter::Trace)]);
+        assert_eq!(filter.level("game"), LevelFilter::Warn);
+        assert_eq!(filter.level("game::audio"), LevelFilter::Warn);
+    }
+
+    #[test]
+    fn filter_matches_module_and_submodules() {
+        let filter = filter(&[("game::ai", LevelFilter::Trace)]);
+        assert_eq!(filter.level("game::ai"), LevelFilter::Trace);
+        assert_eq!(filter.level("game::ai::path"), LevelFilter::Trace);
+    }
+
+    #[test]
+    fn filter_does_not_match_partial_names() {
+        let filter = filter(&[("game::ai", LevelFilter::Trace)]);
+        assert_eq!(filter.level("game::aim"), LevelFilter::Warn);
+        assert_eq!(filter.level("game::a"), LevelFilter::Warn);
+    }
+
+    #[test]
+    fn filter_most_specific_module_wins() {
+        let filter = filter(&[
+            ("game::ai::path", LevelFilter::Off),
+            ("game", LevelFilter::Debug),
+            ("game::ai", LevelFilter::Trace),
+        ]);
+        assert_eq!(filter.level("game::audio"), LevelFilter::Debug);
+        assert_eq!(filter.level("game::ai::steer"), LevelFilter::Trace);
+        assert_eq!(filter.level("game::ai::path::grid"), LevelFilter::Off);
+    }
+
+    #[test]
+    fn filter_max_level() {
+        assert_eq!(filter(&[]).max_level(), LevelFilter::Warn);
+        let filter = filter(&[
+            ("game", LevelFilter::Error),
+            ("game::ai", LevelFilter::Debug),
+        ]);
+        assert_eq!(filter.max_level(), LevelFilter::Debug);
+    }
+
+    fn log_file(name: &str, max_size: u64) -> LogFile {
+        let dir = std::env::temp_dir().join(format!("gdextras-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        LogFile::open(dir.join("game.log"), max_size, 2).unwrap()
+    }
+
+    #[test]
+    fn log_file_rotates_when_full() {
+        let mut file = log_file("rotate", 10);
+        file.write("12345678\n").unwrap();
+        file.write("abc\n").unwrap();
+
+        assert_eq!(
+            fs::read_to_string(file.rotated_path(1)).unwrap(),
+            "12345678\n"
+        );
+        assert_eq!(fs::read_to_string(&file.path).unwrap(), "abc\n");
+        let _ = fs::remove_dir_all(file.path.parent().unwrap());
+    }
+
+    #[test]
+    fn log_file_large_lines_do_not_rotate_empty_file() {
+        let mut file = log_file("large", 4);
+        file.write("larger than max\n").unwrap();
+        assert!(!file.rotated_path(1).exists());
+
+        file.write("again larger\n").unwrap();
+        file.write("small\n").unwrap();
+
+        assert_eq!(
+            fs::read_to_string(file.rotated_path(2)).unwrap(),
+            "larger than max\n"
+        );
+        assert_eq!(
+            fs::read_to_string(file.rotated_path(1)).unwrap(),
+            "again larger\n"
+        );
+        assert_eq!(fs::read_to_string(&file.path).unwrap(), "small\n");
+        let _ = fs::remove_dir_all(file.path.parent().unwrap());
+    }
+}
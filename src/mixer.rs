@@ -0,0 +1,306 @@
+//! Audio bus management
+//!
+//! ```ignore
+//! let mixer = AudioMixer::new();
+//! mixer.set_volume_linear("Music", 0.5)?;
+//! mixer.set_mute("SFX", true)?;
+//!
+//! // User settings
+//! let settings = VolumeSettings::load(VolumeSettings::DEFAULT_PATH)?;
+//! settings.apply(&mixer)?;
+//! ```
+use gdnative::api::{AudioEffect, AudioServer, ConfigFile};
+use gdnative::{GodotError, Ref, Variant};
+
+use crate::error::{Error, Result};
+
+/// Volume in dB used for a linear volume of zero
+pub const SILENCE_DB: f64 = -80.0;
+
+// -----------------------------------------------------------------------------
+//     - Conversion -
+// -----------------------------------------------------------------------------
+/// Convert a linear volume (0.0 - 1.0) to dB.
+pub fn linear_to_db(linear: f32) -> f64 {
+    if linear <= 0.0 {
+        return SILENCE_DB;
+    }
+    (20.0 * (linear as f64).log10()).max(SILENCE_DB)
+}
+
+/// Convert a volume in dB to linear volume.
+pub fn db_to_linear(db: f64) -> f32 {
+    if db <= SILENCE_DB {
+        return 0.0;
+    }
+    10f64.powf(db / 20.0) as f32
+}
+
+// -----------------------------------------------------------------------------
+//     - Mixer -
+// -----------------------------------------------------------------------------
+fn server() -> &'static AudioServer {
+    AudioServer::godot_singleton()
+}
+
+/// Buses are referred to by name, as set up in the Godot audio bus layout.
+pub struct AudioMixer;
+
+impl AudioMixer {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn bus_index(&self, bus: &str) -> Result<i64> {
+        match server().get_bus_index(bus.into()) {
+            -1 => Err(Error::BusNotFound(bus.to_string())),
+            index => Ok(index),
+        }
+    }
+
+    pub fn has_bus(&self, bus: &str) -> bool {
+        self.bus_index(bus).is_ok()
+    }
+
+    pub fn set_volume_db(&self, bus: &str, db: f64) -> Result<()> {
+        let index = self.bus_index(bus)?;
+        server().set_bus_volume_db(index, db);
+        Ok(())
+    }
+
+    pub fn volume_db(&self, bus: &str) -> Result<f64> {
+        let index = self.bus_index(bus)?;
+        Ok(server().get_bus_volume_db(index))
+    }
+
+    /// Set the volume where 0.0 is silent and 1.0 is 0 dB.
+    pub fn set_volume_linear(&self, bus: &str, volume: f32) -> Result<()> {
+        self.set_volume_db(bus, linear_to_db(volume))
+    }
+
+    pub fn volume_linear(&self, bus: &str) -> Result<f32> {
+        self.volume_db(bus).map(db_to_linear)
+    }
+
+    pub fn set_mute(&self, bus: &str, mute: bool) -> Result<()> {
+        let index = self.bus_index(bus)?;
+        server().set_bus_mute(index, mute);
+        Ok(())
+    }
+
+    pub fn is_muted(&self, bus: &str) -> Result<bool> {
+        let index = self.bus_index(bus)?;
+        Ok(server().is_bus_mute(index))
+    }
+
+    pub fn set_solo(&self, bus: &str, solo: bool) -> Result<()> {
+        let index = self.bus_index(bus)?;
+        server().set_bus_solo(index, solo);
+        Ok(())
+    }
+
+    pub fn is_solo(&self, bus: &str) -> Result<bool> {
+        let index = self.bus_index(bus)?;
+        Ok(server().is_bus_solo(index))
+    }
+
+    /// Add an effect at the end of the bus effect chain.
+    /// Returns the index of the effect.
+    pub fn add_effect(&self, bus: &str, effect: Ref<AudioEffect>) -> Result<i64> {
+        let index = self.bus_index(bus)?;
+        server().add_bus_effect(index, effect, -1);
+        Ok(server().get_bus_effect_count(index) - 1)
+    }
+
+    pub fn remove_effect(&self, bus: &str, effect: i64) -> Result<()> {
+        let index = self.effect_bus_index(bus, effect)?;
+        server().remove_bus_effect(index, effect);
+        Ok(())
+    }
+
+    pub fn set_effect_enabled(&self, bus: &str, effect: i64, enabled: bool) -> Result<()> {
+        let index = self.effect_bus_index(bus, effect)?;
+        server().set_bus_effect_enabled(index, effect, enabled);
+        Ok(())
+    }
+
+    pub fn effect_count(&self, bus: &str) -> Result<i64> {
+        let index = self.bus_index(bus)?;
+        Ok(server().get_bus_effect_count(index))
+    }
+
+    fn effect_bus_index(&self, bus: &str, effect: i64) -> Result<i64> {
+        let index = self.bus_index(bus)?;
+        if effect < 0 || effect >= server().get_bus_effect_count(index) {
+            return Err(Error::EffectNotFound {
+                bus: bus.to_string(),
+                index: effect,
+            });
+        }
+        Ok(index)
+    }
+}
+
+impl Default for AudioMixer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Settings -
+// -----------------------------------------------------------------------------
+/// User volume settings, as linear volumes between 0.0 and 1.0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VolumeSettings {
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+    pub voice: f32,
+}
+
+impl Default for VolumeSettings {
+    fn default() -> Self {
+        Self {
+            master: 1.0,
+            music: 1.0,
+            sfx: 1.0,
+            voice: 1.0,
+        }
+    }
+}
+
+impl VolumeSettings {
+    pub const DEFAULT_PATH: &'static str = "user://settings.cfg";
+
+    pub const MASTER_BUS: &'static str = "Master";
+    pub const MUSIC_BUS: &'static str = "Music";
+    pub const SFX_BUS: &'static str = "SFX";
+    pub const VOICE_BUS: &'static str = "Voice";
+
+    const SECTION: &'static str = "audio";
+
+    fn buses(&self) -> [(&'static str, f32); 4] {
+        [
+            (Self::MASTER_BUS, self.master),
+            (Self::MUSIC_BUS, self.music),
+            (Self::SFX_BUS, self.sfx),
+            (Self::VOICE_BUS, self.voice),
+        ]
+    }
+
+    /// Set the bus volumes.
+    /// Buses that are not in the bus layout are skipped.
+    pub fn apply(&self, mixer: &AudioMixer) -> Result<()> {
+        for (bus, volume) in self.buses().iter() {
+            if mixer.has_bus(bus) {
+                mixer.set_volume_linear(bus, *volume)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Load the settings from a config file.
+    /// Returns the default settings if the file does not exist.
+    pub fn load(path: &str) -> Result<Self> {
+        let config = ConfigFile::new();
+        match config.load(path.into()) {
+            Ok(()) => (),
+            Err(GodotError::FileNotFound) => return Ok(Self::default()),
+            Err(error) => {
+                return Err(Error::File {
+                    path: path.to_string(),
+                    error,
+                })
+            }
+        }
+
+        let defaults = Self::default();
+        let get = |key: &str, default: f32| {
+            config
+                .get_value(
+                    Self::SECTION.into(),
+                    key.into(),
+                    Variant::from_f64(default as f64),
+                )
+                .try_to_f64()
+                .map(|v| (v as f32).clamp(0.0, 1.0))
+                .unwrap_or(default)
+        };
+
+        Ok(Self {
+            master: get("master", defaults.master),
+            music: get("music", defaults.music),
+            sfx: get("sfx", defaults.sfx),
+            voice: get("voice", defaults.voice),
+        })
+    }
+
+    /// Save the settings to a config file.
+    /// Other sections in the file are kept.
+    pub fn save(&self, path: &str) -> Result<()> {
+        let config = ConfigFile::new();
+        match config.load(path.into()) {
+            Ok(()) | Err(GodotError::FileNotFound) => (),
+            Err(error) => {
+                return Err(Error::File {
+                    path: path.to_string(),
+                    error,
+                })
+            }
+        }
+
+        let values = [
+            ("master", self.master),
+            ("music", self.music),
+            ("sfx", self.sfx),
+            ("voice", self.voice),
+        ];
+
+        for (key, value) in values.iter() {
+            config.set_value(
+                Self::SECTION.into(),
+                (*key).into(),
+                Variant::from_f64(*value as f64),
+            );
+        }
+
+        config.save(path.into()).map_err(|error| Error::File {
+            path: path.to_string(),
+            error,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn silence_is_minus_80_db() {
+        assert_eq!(linear_to_db(0.0), -80.0);
+        assert_eq!(linear_to_db(-1.0), -80.0);
+        assert_eq!(db_to_linear(-80.0), 0.0);
+        assert_eq!(db_to_linear(-120.0), 0.0);
+    }
+
+    #[test]
+    fn full_volume_is_0_db() {
+        assert_eq!(linear_to_db(1.0), 0.0);
+        assert_eq!(db_to_linear(0.0), 1.0);
+    }
+
+    #[test]
+    fn conversion_round_trips() {
+        for &linear in &[0.01, 0.1, 0.25, 0.5, 0.75, 1.0, 2.0] {
+            let back = db_to_linear(linear_to_db(linear));
+            assert!((back - linear).abs() < 1e-5, "{} became {}", linear, back);
+        }
+
+        for &db in &[-60.0, -24.0, -6.0, 0.0, 6.0] {
+            let back = linear_to_db(db_to_linear(db));
+            assert!((back - db).abs() < 1e-4, "{} became {}", db, back);
+        }
+    }
+}
@@ -4,6 +4,8 @@ use gdnative::{Ref, Vector2, Vector3};
 use euclid::Rotation3D as Rot3D;
 use euclid::{Transform3D, UnknownUnit};
 
+use crate::thread::ThreadBound;
+
 type Transform3 = Transform3D<f32, UnknownUnit, UnknownUnit>;
 pub type Rotation3 = Rot3D<f32, UnknownUnit, UnknownUnit>;
 
@@ -160,21 +162,25 @@ impl Move3D for KinematicBody {
 ///
 /// `set_rotation` takes four positive values, so a combination of inpux axis strenghts
 /// can be used.
+///
+/// The rotation can only be used on the thread that created it.
 pub struct Rotation2D {
     aim_direction: Option<Vector2>,
-    owner: Node2D,
+    owner: ThreadBound<Ref<Node2D>>,
 }
 
-unsafe impl Send for Rotation2D {}
-
 impl Rotation2D {
-    pub fn new(owner: Node2D) -> Self {
+    pub fn new(owner: Ref<Node2D>) -> Self {
         Self {
             aim_direction: None,
-            owner,
+            owner: ThreadBound::new(owner),
         }
     }
 
+    fn owner(&self) -> &Node2D {
+        unsafe { self.owner.assume_safe() }.as_ref()
+    }
+
     // pub fn set_rotation(&self, left: f32, right: f32, up: f32, down: f32) {
     //     let dir = Vector2::new(-left + right, -up + down);
     //     if dir == Vector2::zero() {
@@ -185,19 +191,203 @@ impl Rotation2D {
     // }
 
     pub fn follow_mouse(&mut self) {
-        let mouse_pos = self.owner.get_global_mouse_position();
-        self.owner.look_at(mouse_pos);
+        let owner = self.owner();
+        let mouse_pos = owner.get_global_mouse_position();
+        owner.look_at(mouse_pos);
     }
 
-    pub unsafe fn update_rotation(&mut self) -> Option<()> {
+    pub fn update_rotation(&mut self) -> Option<()> {
         let aim_dir = self.aim_direction?;
         // let rot = self.owner.get_rotation();
         let new_rot = aim_dir.y.atan2(aim_dir.x) as f64;
-        self.owner.set_rotation(new_rot);
+        self.owner().set_rotation(new_rot);
         Some(())
     }
 }
 
+// -----------------------------------------------------------------------------
+//     - Direction -
+// -----------------------------------------------------------------------------
+/// One of eight directions, in screen space (down is positive y).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Right,
+    DownRight,
+    Down,
+    DownLeft,
+    Left,
+    UpLeft,
+    Up,
+    UpRight,
+}
+
+impl Direction {
+    /// All directions, clockwise from `Right`.
+    pub const ALL: [Direction; 8] = [
+        Direction::Right,
+        Direction::DownRight,
+        Direction::Down,
+        Direction::DownLeft,
+        Direction::Left,
+        Direction::UpLeft,
+        Direction::Up,
+        Direction::UpRight,
+    ];
+
+    /// The closest of the eight directions.
+    /// Returns `None` for a zero vector.
+    pub fn from_vector(v: Vector2) -> Option<Self> {
+        if v.x == 0.0 && v.y == 0.0 {
+            return None;
+        }
+
+        let octant = (v.y.atan2(v.x) / std::f32::consts::FRAC_PI_4).round() as i32;
+        Some(Self::ALL[octant.rem_euclid(8) as usize])
+    }
+
+    /// The closest of the four directions `Right`, `Down`, `Left` and `Up`.
+    /// Returns `None` for a zero vector.
+    pub fn from_vector_4(v: Vector2) -> Option<Self> {
+        if v.x == 0.0 && v.y == 0.0 {
+            return None;
+        }
+
+        let direction = if v.x.abs() >= v.y.abs() {
+            if v.x > 0.0 {
+                Direction::Right
+            } else {
+                Direction::Left
+            }
+        } else if v.y > 0.0 {
+            Direction::Down
+        } else {
+            Direction::Up
+        };
+        Some(direction)
+    }
+
+    /// Unit vector pointing in the direction.
+    pub fn to_vector(self) -> Vector2 {
+        let d = std::f32::consts::FRAC_1_SQRT_2;
+        match self {
+            Direction::Right => Vector2::new(1.0, 0.0),
+            Direction::DownRight => Vector2::new(d, d),
+            Direction::Down => Vector2::new(0.0, 1.0),
+            Direction::DownLeft => Vector2::new(-d, d),
+            Direction::Left => Vector2::new(-1.0, 0.0),
+            Direction::UpLeft => Vector2::new(-d, -d),
+            Direction::Up => Vector2::new(0.0, -1.0),
+            Direction::UpRight => Vector2::new(d, -d),
+        }
+    }
+
+    /// Suffix used in animation names, e.g. `down_left`.
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Direction::Right => "right",
+            Direction::DownRight => "down_right",
+            Direction::Down => "down",
+            Direction::DownLeft => "down_left",
+            Direction::Left => "left",
+            Direction::UpLeft => "up_left",
+            Direction::Up => "up",
+            Direction::UpRight => "up_right",
+        }
+    }
+
+    /// Mirror the direction horizontally, e.g. `UpLeft` becomes `UpRight`.
+    pub fn flip_h(self) -> Self {
+        match self {
+            Direction::Right => Direction::Left,
+            Direction::DownRight => Direction::DownLeft,
+            Direction::DownLeft => Direction::DownRight,
+            Direction::Left => Direction::Right,
+            Direction::UpLeft => Direction::UpRight,
+            Direction::UpRight => Direction::UpLeft,
+            vertical => vertical,
+        }
+    }
+
+    pub fn is_diagonal(self) -> bool {
+        matches!(
+            self,
+            Direction::DownRight | Direction::DownLeft | Direction::UpLeft | Direction::UpRight
+        )
+    }
+
+    /// The horizontal and vertical parts of a diagonal direction.
+    pub fn split(self) -> (Option<Self>, Option<Self>) {
+        match self {
+            Direction::Right | Direction::Left => (Some(self), None),
+            Direction::Up | Direction::Down => (None, Some(self)),
+            Direction::DownRight => (Some(Direction::Right), Some(Direction::Down)),
+            Direction::DownLeft => (Some(Direction::Left), Some(Direction::Down)),
+            Direction::UpLeft => (Some(Direction::Left), Some(Direction::Up)),
+            Direction::UpRight => (Some(Direction::Right), Some(Direction::Up)),
+        }
+    }
+}
+
+/// Keep track of which way a character is facing.
+/// The direction is kept while the character is standing still.
+///
+/// ```ignore
+/// self.velocity = owner.move_and_slide_default(self.velocity, UP_2D);
+/// let facing = self.facing.update(self.velocity);
+/// sprite.play_directional("run", facing)?;
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Facing {
+    direction: Direction,
+    min_speed: f32,
+    eight_way: bool,
+}
+
+impl Facing {
+    pub fn new(direction: Direction) -> Self {
+        Self {
+            direction,
+            min_speed: 1.0,
+            eight_way: true,
+        }
+    }
+
+    /// Ignore velocities slower than this.
+    pub fn with_min_speed(mut self, min_speed: f32) -> Self {
+        self.min_speed = min_speed;
+        self
+    }
+
+    /// Only face `Right`, `Down`, `Left` and `Up`.
+    pub fn with_four_way(mut self) -> Self {
+        self.eight_way = false;
+        self
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// Face the direction of the velocity, unless it is too slow.
+    pub fn update(&mut self, velocity: Vector2) -> Direction {
+        if velocity.square_length() < self.min_speed * self.min_speed {
+            return self.direction;
+        }
+
+        let direction = if self.eight_way {
+            Direction::from_vector(velocity)
+        } else {
+            Direction::from_vector_4(velocity)
+        };
+
+        if let Some(direction) = direction {
+            self.direction = direction;
+        }
+
+        self.direction
+    }
+}
+
 // -----------------------------------------------------------------------------
 //     - Rotation 3D -
 // -----------------------------------------------------------------------------
@@ -254,3 +444,125 @@ pub fn transform_to_x_y_z_direction(trans: Transform3) -> (Vector3, Vector3, Vec
 //         }
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at_degrees(degrees: f32) -> Vector2 {
+        let radians = degrees.to_radians();
+        Vector2::new(radians.cos(), radians.sin())
+    }
+
+    #[test]
+    fn from_vector_zero_is_none() {
+        assert_eq!(Direction::from_vector(Vector2::new(0.0, 0.0)), None);
+        assert_eq!(Direction::from_vector_4(Vector2::new(0.0, 0.0)), None);
+    }
+
+    #[test]
+    fn from_vector_matches_to_vector() {
+        for &direction in &Direction::ALL {
+            assert_eq!(
+                Direction::from_vector(direction.to_vector()),
+                Some(direction)
+            );
+            assert_eq!(
+                Direction::from_vector(direction.to_vector() * 10.0),
+                Some(direction)
+            );
+        }
+    }
+
+    #[test]
+    fn from_vector_octant_boundaries() {
+        let cases = [
+            (22.0, Direction::Right),
+            (23.0, Direction::DownRight),
+            (67.0, Direction::DownRight),
+            (68.0, Direction::Down),
+            (157.0, Direction::DownLeft),
+            (158.0, Direction::Left),
+            (-22.0, Direction::Right),
+            (-23.0, Direction::UpRight),
+            (-157.0, Direction::UpLeft),
+            (-158.0, Direction::Left),
+        ];
+
+        for &(degrees, direction) in &cases {
+            assert_eq!(
+                Direction::from_vector(at_degrees(degrees)),
+                Some(direction),
+                "{} degrees",
+                degrees
+            );
+        }
+    }
+
+    #[test]
+    fn from_vector_left_with_negative_zero() {
+        assert_eq!(
+            Direction::from_vector(Vector2::new(-1.0, -0.0)),
+            Some(Direction::Left)
+        );
+        assert_eq!(
+            Direction::from_vector(Vector2::new(-1.0, 0.0)),
+            Some(Direction::Left)
+        );
+    }
+
+    #[test]
+    fn from_vector_4_prefers_horizontal_on_ties() {
+        assert_eq!(
+            Direction::from_vector_4(Vector2::new(1.0, 1.0)),
+            Some(Direction::Right)
+        );
+        assert_eq!(
+            Direction::from_vector_4(Vector2::new(-1.0, -1.0)),
+            Some(Direction::Left)
+        );
+        assert_eq!(
+            Direction::from_vector_4(Vector2::new(0.5, -1.0)),
+            Some(Direction::Up)
+        );
+        assert_eq!(
+            Direction::from_vector_4(Vector2::new(-0.5, 1.0)),
+            Some(Direction::Down)
+        );
+    }
+
+    #[test]
+    fn flip_h_mirrors_horizontally() {
+        assert_eq!(Direction::UpLeft.flip_h(), Direction::UpRight);
+        assert_eq!(Direction::Right.flip_h(), Direction::Left);
+        assert_eq!(Direction::Up.flip_h(), Direction::Up);
+        for &direction in &Direction::ALL {
+            assert_eq!(direction.flip_h().flip_h(), direction);
+        }
+    }
+
+    #[test]
+    fn split_diagonals() {
+        assert_eq!(
+            Direction::DownLeft.split(),
+            (Some(Direction::Left), Some(Direction::Down))
+        );
+        assert_eq!(Direction::Up.split(), (None, Some(Direction::Up)));
+        assert_eq!(Direction::Right.split(), (Some(Direction::Right), None));
+    }
+
+    #[test]
+    fn facing_keeps_direction_when_slow() {
+        let mut facing = Facing::new(Direction::Down).with_min_speed(10.0);
+        assert_eq!(facing.update(Vector2::new(-5.0, 0.0)), Direction::Down);
+        assert_eq!(facing.update(Vector2::new(-20.0, 0.0)), Direction::Left);
+        assert_eq!(facing.update(Vector2::new(0.0, 0.0)), Direction::Left);
+    }
+
+    #[test]
+    fn facing_four_way() {
+        let mut facing = Facing::new(Direction::Down).with_four_way();
+        assert_eq!(facing.update(Vector2::new(20.0, -10.0)), Direction::Right);
+        assert_eq!(facing.update(Vector2::new(10.0, -20.0)), Direction::Up);
+    }
+}
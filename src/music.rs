@@ -0,0 +1,701 @@
+//! Music player
+//! Crossfading between tracks, playlists and layered stems.
+//!
+//! This requires the `MusicPlayer` class to be registered, and a `MusicPlayer`
+//! node in the tree (e.g. as an autoload).
+//!
+//! ```ignore
+//! let track = Track::load_with_intro("res://music/boss_intro.ogg", "res://music/boss.ogg")?;
+//! music.crossfade_to(track, 3.0);
+//!
+//! // Vertical layering
+//! music.play_layered(owner, vec![
+//!     Stem::load("res://music/drums.ogg", 0.0)?,
+//!     Stem::load("res://music/bass.ogg", 0.3)?,
+//!     Stem::load("res://music/lead.ogg", 0.7)?,
+//! ]);
+//! music.set_intensity(0.5);
+//! ```
+//!
+//! Streams are restarted by the player when they finish,
+//! so they don't need to be imported with looping enabled.
+use gdnative::api::{AudioStream, AudioStreamPlayer, Node};
+use gdnative::{methods, NativeClass, Ref, ToVariant, VariantArray};
+use rand::seq::SliceRandom;
+
+use crate::error::{Error, GdResultExt, Result};
+use crate::gd_err;
+use crate::mixer::linear_to_db;
+use crate::resource::load_resource;
+
+// -----------------------------------------------------------------------------
+//     - Tracks -
+// -----------------------------------------------------------------------------
+/// A music track, with an optional intro that plays once before the loop.
+#[derive(Clone)]
+pub struct Track {
+    pub intro: Option<Ref<AudioStream>>,
+    pub stream: Ref<AudioStream>,
+}
+
+impl Track {
+    pub fn new(stream: Ref<AudioStream>) -> Self {
+        Self {
+            intro: None,
+            stream,
+        }
+    }
+
+    pub fn with_intro(intro: Ref<AudioStream>, stream: Ref<AudioStream>) -> Self {
+        Self {
+            intro: Some(intro),
+            stream,
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self> {
+        Ok(Self::new(load_resource(path)?))
+    }
+
+    pub fn load_with_intro(intro: &str, path: &str) -> Result<Self> {
+        Ok(Self::with_intro(
+            load_resource(intro)?,
+            load_resource(path)?,
+        ))
+    }
+}
+
+/// A layer of a layered track.
+/// The stem is audible when the intensity is at or above the threshold.
+#[derive(Clone)]
+pub struct Stem {
+    pub stream: Ref<AudioStream>,
+    pub threshold: f32,
+}
+
+impl Stem {
+    pub fn new(stream: Ref<AudioStream>, threshold: f32) -> Self {
+        Self { stream, threshold }
+    }
+
+    pub fn load(path: &str, threshold: f32) -> Result<Self> {
+        Ok(Self::new(load_resource(path)?, threshold))
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Playlist -
+// -----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaylistMode {
+    Sequential,
+    Shuffle,
+}
+
+fn shuffle_random(order: &mut [usize]) {
+    order.shuffle(&mut rand::thread_rng());
+}
+
+/// Order in which the tracks of a playlist are played, as track indices.
+struct PlayOrder {
+    mode: PlaylistMode,
+    order: Vec<usize>,
+    position: usize,
+    shuffle: fn(&mut [usize]),
+}
+
+impl PlayOrder {
+    fn new(len: usize, mode: PlaylistMode, shuffle: fn(&mut [usize])) -> Self {
+        let mut order = Self {
+            mode,
+            order: (0..len).collect(),
+            position: 0,
+            shuffle,
+        };
+        order.reorder(None);
+        order
+    }
+
+    fn reorder(&mut self, last: Option<usize>) {
+        if self.mode == PlaylistMode::Sequential {
+            return;
+        }
+
+        (self.shuffle)(&mut self.order);
+
+        // Don't play the same track twice in a row
+        if self.order.len() > 1 && self.order.first().cloned() == last {
+            let end = self.order.len() - 1;
+            self.order.swap(0, end);
+        }
+    }
+
+    fn next(&mut self) -> Option<usize> {
+        if self.order.is_empty() {
+            return None;
+        }
+
+        if self.position >= self.order.len() {
+            let last = self.order.last().cloned();
+            self.position = 0;
+            self.reorder(last);
+        }
+
+        let index = self.order[self.position];
+        self.position += 1;
+        Some(index)
+    }
+}
+
+struct Playlist {
+    tracks: Vec<Track>,
+    order: PlayOrder,
+}
+
+impl Playlist {
+    fn new(tracks: Vec<Track>, mode: PlaylistMode) -> Self {
+        Self {
+            order: PlayOrder::new(tracks.len(), mode, shuffle_random),
+            tracks,
+        }
+    }
+
+    fn next(&mut self) -> Option<Track> {
+        let index = self.order.next()?;
+        Some(self.tracks[index].clone())
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Fade -
+// -----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy)]
+struct Fade {
+    from: f32,
+    to: f32,
+    duration: f64,
+    elapsed: f64,
+}
+
+impl Fade {
+    fn new(from: f32, to: f32, duration: f64) -> Self {
+        Self {
+            from,
+            to,
+            duration,
+            elapsed: 0.0,
+        }
+    }
+
+    fn update(&mut self, delta: f64) -> f32 {
+        self.elapsed += delta;
+        if self.is_done() {
+            return self.to;
+        }
+
+        let t = (self.elapsed / self.duration) as f32;
+        self.from + (self.to - self.from) * t
+    }
+
+    fn is_done(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+struct Channel {
+    player: Ref<AudioStreamPlayer>,
+    volume: f32,
+    fade: Option<Fade>,
+}
+
+impl Channel {
+    fn player(&self) -> &AudioStreamPlayer {
+        unsafe { self.player.assume_safe() }.as_ref()
+    }
+
+    fn fade_to(&mut self, volume: f32, duration: f64) {
+        if duration <= 0.0 {
+            self.volume = volume;
+            self.fade = None;
+        } else {
+            self.fade = Some(Fade::new(self.volume, volume, duration));
+        }
+    }
+
+    /// Update the fade, returns true if the channel faded out.
+    fn update(&mut self, delta: f64, master: f32) -> bool {
+        let mut faded_out = false;
+
+        if let Some(fade) = self.fade.as_mut() {
+            self.volume = fade.update(delta);
+            if fade.is_done() {
+                faded_out = fade.to <= 0.0;
+                self.fade = None;
+            }
+        }
+
+        self.player()
+            .set_volume_db(linear_to_db(self.volume * master));
+        faded_out
+    }
+}
+
+struct Deck {
+    channel: Channel,
+    track: Option<Track>,
+    in_intro: bool,
+    /// The playlist already moved on from this track
+    advanced: bool,
+}
+
+impl Deck {
+    fn start(&mut self, track: Track) {
+        let player = self.channel.player();
+        self.in_intro = track.intro.is_some();
+        self.advanced = false;
+        player.set_stream(track.intro.clone().unwrap_or_else(|| track.stream.clone()));
+        player.play(0.0);
+        self.track = Some(track);
+    }
+
+    fn stop(&mut self) {
+        self.channel.player().stop();
+        self.channel.fade = None;
+        self.channel.volume = 0.0;
+        self.track = None;
+    }
+
+    /// Length in seconds of the looping part of the track.
+    fn length(&self) -> Option<f64> {
+        let track = self.track.as_ref()?;
+        if self.in_intro {
+            return None;
+        }
+
+        let length = unsafe { track.stream.assume_safe() }.get_length();
+        if length > 0.0 {
+            Some(length)
+        } else {
+            None
+        }
+    }
+
+    /// Seconds left of the looping part of the track.
+    fn remaining(&self) -> Option<f64> {
+        let length = self.length()?;
+        Some(length - self.channel.player().get_playback_position())
+    }
+}
+
+struct Layer {
+    channel: Channel,
+    threshold: f32,
+}
+
+// -----------------------------------------------------------------------------
+//     - Music player -
+// -----------------------------------------------------------------------------
+#[derive(NativeClass)]
+#[inherit(Node)]
+pub struct MusicPlayer {
+    #[property(path = "music/bus")]
+    bus: String,
+    /// Crossfade duration used by `play` and playlists
+    #[property(path = "music/crossfade", default = 2.0)]
+    crossfade: f64,
+    /// Fade duration when stems are enabled or disabled
+    #[property(path = "music/layer_fade", default = 1.0)]
+    layer_fade: f64,
+    volume: f32,
+    decks: Vec<Deck>,
+    active: usize,
+    playlist: Option<Playlist>,
+    layers: Vec<Layer>,
+    intensity: f32,
+}
+
+#[methods]
+impl MusicPlayer {
+    fn new(_owner: &Node) -> Self {
+        Self {
+            bus: "Music".into(),
+            crossfade: 2.0,
+            layer_fade: 1.0,
+            volume: 1.0,
+            decks: Vec::new(),
+            active: 0,
+            playlist: None,
+            layers: Vec::new(),
+            intensity: 0.0,
+        }
+    }
+
+    #[export]
+    fn _ready(&mut self, owner: &Node) {
+        for index in 0..2 {
+            let player = self.create_player(owner, "on_deck_finished", index);
+            self.decks.push(Deck {
+                channel: Channel {
+                    player,
+                    volume: 0.0,
+                    fade: None,
+                },
+                track: None,
+                in_intro: false,
+                advanced: false,
+            });
+        }
+    }
+
+    fn create_player(
+        &self,
+        owner: &Node,
+        on_finished: &str,
+        index: usize,
+    ) -> Ref<AudioStreamPlayer> {
+        let player = AudioStreamPlayer::new();
+        player.set_bus(self.bus.as_str().into());
+        let player = player.into_shared();
+        let p = unsafe { player.assume_safe() };
+        owner.add_child(p, false);
+
+        let binds = VariantArray::new();
+        binds.push(&(index as i64).to_variant());
+        let _ = p
+            .connect(
+                "finished".into(),
+                owner,
+                on_finished.into(),
+                binds.into_shared(),
+                0,
+            )
+            .map_err(|error| Error::Signal {
+                signal: "finished".to_string(),
+                error,
+            })
+            .log_err();
+
+        player
+    }
+
+    /// Master volume of the music player, between 0.0 and 1.0.
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.clamp(0.0, 1.0);
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    /// Crossfade to a track using the default crossfade duration.
+    pub fn play(&mut self, track: Track) {
+        self.crossfade_to(track, self.crossfade);
+    }
+
+    /// Crossfade from the current track to a new track.
+    pub fn crossfade_to(&mut self, track: Track, duration: f64) {
+        self.stop_layers(duration);
+
+        if self.decks.is_empty() {
+            gd_err!("music player is not ready");
+            return;
+        }
+
+        let previous = self.active;
+        self.active = (self.active + 1) % self.decks.len();
+
+        if self.decks[previous].track.is_some() {
+            self.decks[previous].channel.fade_to(0.0, duration);
+            if duration <= 0.0 {
+                self.decks[previous].stop();
+            }
+        }
+
+        let deck = &mut self.decks[self.active];
+        deck.channel.volume = 0.0;
+        deck.channel.fade_to(1.0, duration);
+        deck.start(track);
+    }
+
+    /// Fade out all music.
+    pub fn stop(&mut self, duration: f64) {
+        self.playlist = None;
+        self.stop_layers(duration);
+
+        for deck in self.decks.iter_mut().filter(|d| d.track.is_some()) {
+            deck.channel.fade_to(0.0, duration);
+            if duration <= 0.0 {
+                deck.stop();
+            }
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    //     - Playlist -
+    // -------------------------------------------------------------------------
+    /// Play a list of tracks, crossfading between them.
+    pub fn play_playlist(&mut self, tracks: Vec<Track>, mode: PlaylistMode) {
+        self.playlist = Some(Playlist::new(tracks, mode));
+        self.next();
+    }
+
+    /// Crossfade to the next track of the playlist.
+    pub fn next(&mut self) {
+        let track = match self.playlist.as_mut().and_then(Playlist::next) {
+            Some(track) => track,
+            None => return,
+        };
+        self.play(track);
+    }
+
+    #[export]
+    fn on_deck_finished(&mut self, _owner: &Node, index: i64) {
+        let index = index as usize;
+        let is_active = index == self.active;
+        let deck = match self.decks.get_mut(index) {
+            Some(deck) => deck,
+            None => return,
+        };
+
+        let track = match &deck.track {
+            Some(track) => track.clone(),
+            None => return,
+        };
+
+        if deck.in_intro {
+            deck.in_intro = false;
+            let player = deck.channel.player();
+            player.set_stream(track.stream);
+            player.play(0.0);
+            return;
+        }
+
+        if is_active && self.playlist.is_some() && !deck.advanced {
+            self.next();
+            return;
+        }
+
+        // Loop
+        deck.channel.player().play(0.0);
+    }
+
+    // -------------------------------------------------------------------------
+    //     - Layers -
+    // -------------------------------------------------------------------------
+    /// Play stems in sync, stopping the current track.
+    /// Stems are enabled based on the current intensity.
+    pub fn play_layered(&mut self, owner: &Node, stems: Vec<Stem>) {
+        self.playlist = None;
+        for deck in self.decks.iter_mut().filter(|d| d.track.is_some()) {
+            deck.channel.fade_to(0.0, self.crossfade);
+        }
+
+        self.clear_layers();
+
+        for (index, stem) in stems.into_iter().enumerate() {
+            let player = self.create_player(owner, "on_layer_finished", index);
+            let mut channel = Channel {
+                player,
+                volume: 0.0,
+                fade: None,
+            };
+
+            if self.intensity >= stem.threshold {
+                channel.fade_to(1.0, self.layer_fade);
+            }
+
+            let p = channel.player();
+            p.set_volume_db(linear_to_db(0.0));
+            p.set_stream(stem.stream);
+
+            self.layers.push(Layer {
+                channel,
+                threshold: stem.threshold,
+            });
+        }
+
+        // Start all stems together so they stay in sync
+        for layer in &self.layers {
+            layer.channel.player().play(0.0);
+        }
+    }
+
+    /// Set the intensity, fading stems in or out.
+    pub fn set_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity;
+
+        for layer in &mut self.layers {
+            let target = if intensity >= layer.threshold {
+                1.0
+            } else {
+                0.0
+            };
+            let current_target = layer
+                .channel
+                .fade
+                .map(|f| f.to)
+                .unwrap_or(layer.channel.volume);
+            if (current_target - target).abs() > std::f32::EPSILON {
+                layer.channel.fade_to(target, self.layer_fade);
+            }
+        }
+    }
+
+    pub fn intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    fn stop_layers(&mut self, duration: f64) {
+        if duration <= 0.0 {
+            self.clear_layers();
+            return;
+        }
+
+        for layer in &mut self.layers {
+            layer.channel.fade_to(0.0, duration);
+            // Keep the layer from fading in again
+            layer.threshold = std::f32::INFINITY;
+        }
+    }
+
+    fn clear_layers(&mut self) {
+        for layer in self.layers.drain(..) {
+            unsafe { layer.channel.player.assume_safe() }.queue_free();
+        }
+    }
+
+    #[export]
+    fn on_layer_finished(&mut self, _owner: &Node, _index: i64) {
+        // Restart every stem once they have all ended, to stay in sync
+        if self.layers.iter().any(|l| l.channel.player().is_playing()) {
+            return;
+        }
+
+        for layer in &self.layers {
+            layer.channel.player().play(0.0);
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    //     - Process -
+    // -------------------------------------------------------------------------
+    #[export]
+    fn _process(&mut self, _owner: &Node, delta: f64) {
+        let volume = self.volume;
+
+        for deck in &mut self.decks {
+            if deck.track.is_some() && deck.channel.update(delta, volume) {
+                deck.stop();
+            }
+        }
+
+        for layer in &mut self.layers {
+            layer.channel.update(delta, volume);
+        }
+
+        if self
+            .layers
+            .iter()
+            .all(|l| l.threshold.is_infinite() && l.channel.fade.is_none())
+        {
+            self.clear_layers();
+        }
+
+        // Start the next track early enough to crossfade, once per track.
+        // Short tracks start crossfading halfway through.
+        if self.playlist.is_none() || self.crossfade <= 0.0 {
+            return;
+        }
+
+        let crossfade = self.crossfade;
+        let deck = match self.decks.get_mut(self.active) {
+            Some(deck) if !deck.advanced => deck,
+            _ => return,
+        };
+
+        if let (Some(length), Some(remaining)) = (deck.length(), deck.remaining()) {
+            if remaining <= crossfade.min(length / 2.0) {
+                deck.advanced = true;
+                self.next();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reverse(order: &mut [usize]) {
+        order.reverse();
+    }
+
+    fn take(order: &mut PlayOrder, count: usize) -> Vec<usize> {
+        (0..count).filter_map(|_| order.next()).collect()
+    }
+
+    #[test]
+    fn sequential_order_wraps() {
+        let mut order = PlayOrder::new(3, PlaylistMode::Sequential, reverse);
+        assert_eq!(take(&mut order, 7), vec![0, 1, 2, 0, 1, 2, 0]);
+    }
+
+    #[test]
+    fn empty_order_has_no_next() {
+        let mut order = PlayOrder::new(0, PlaylistMode::Shuffle, reverse);
+        assert_eq!(order.next(), None);
+    }
+
+    #[test]
+    fn shuffle_plays_every_track_per_round() {
+        let mut order = PlayOrder::new(4, PlaylistMode::Shuffle, shuffle_random);
+
+        for _ in 0..10 {
+            let mut round = take(&mut order, 4);
+            round.sort_unstable();
+            assert_eq!(round, vec![0, 1, 2, 3]);
+        }
+    }
+
+    #[test]
+    fn shuffle_does_not_repeat_across_rounds() {
+        // Reversing each round would start with the track that just played
+        let mut order = PlayOrder::new(3, PlaylistMode::Shuffle, reverse);
+        let played = take(&mut order, 12);
+
+        assert_eq!(&played[..6], &[2, 1, 0, 2, 1, 0]);
+        assert!(played.windows(2).all(|w| w[0] != w[1]), "{:?}", played);
+    }
+
+    #[test]
+    fn shuffle_single_track_repeats() {
+        let mut order = PlayOrder::new(1, PlaylistMode::Shuffle, shuffle_random);
+        assert_eq!(take(&mut order, 3), vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn fade_interpolates_linearly() {
+        let mut fade = Fade::new(0.0, 1.0, 2.0);
+        assert_eq!(fade.update(0.5), 0.25);
+        assert_eq!(fade.update(0.5), 0.5);
+        assert!(!fade.is_done());
+
+        assert_eq!(fade.update(1.5), 1.0);
+        assert!(fade.is_done());
+    }
+
+    #[test]
+    fn fade_out() {
+        let mut fade = Fade::new(0.8, 0.0, 1.0);
+        assert!((fade.update(0.25) - 0.6).abs() < 1e-6);
+        assert_eq!(fade.update(1.0), 0.0);
+        assert!(fade.is_done());
+    }
+
+    #[test]
+    fn zero_duration_fade_is_done() {
+        let mut fade = Fade::new(1.0, 0.0, 0.0);
+        assert_eq!(fade.update(0.0), 0.0);
+        assert!(fade.is_done());
+    }
+}
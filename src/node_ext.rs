@@ -1,34 +1,154 @@
 use gdnative::api::{
-    Camera, Camera2D, Control, KinematicBody, KinematicBody2D, Label, Node, Node2D, Particles,
-    Spatial,
+    Camera, Camera2D, Control, KinematicBody, KinematicBody2D, Label, Node, Node2D, PackedScene,
+    Particles, Spatial,
 };
-use gdnative::{GodotObject, MapMut, NativeClass, UserData, Vector2};
+use gdnative::{
+    GodotObject, ManuallyManaged, MapMut, NativeClass, Ref, SubClass, UserData, Vector2,
+};
+
+use crate::callback::call_after;
+use crate::error::{Error, Result};
+use crate::gd_panic;
+use crate::resource::load_resource;
 
+// -----------------------------------------------------------------------------
+//     - Node extension -
+// -----------------------------------------------------------------------------
 pub trait NodeExt: GodotObject + std::fmt::Debug {
-    fn get_and_cast<T: GodotObject>(&self, path: &str) -> &T;
+    /// Get a node and cast it to `T`.
+    /// Panics if there is no node at the path, or if it is not a `T`.
+    fn get_and_cast<T: GodotObject>(&self, path: &str) -> &T {
+        match self.try_get_and_cast(path) {
+            Ok(node) => node,
+            Err(e) => gd_panic!("{}", e),
+        }
+    }
+
+    /// Get a node and cast it to `T`.
+    fn try_get_and_cast<T: GodotObject>(&self, path: &str) -> Result<&T> {
+        let node = self
+            .as_node()
+            .get_node(path.into())
+            .ok_or_else(|| Error::NodeNotFound(path.to_string()))?;
 
-    fn with_script<T, U, V, F>(&self, f: F)
+        unsafe { node.assume_safe() }
+            .as_ref()
+            .cast::<T>()
+            .ok_or_else(|| Error::Cast {
+                path: path.to_string(),
+                expected: T::class_name(),
+            })
+    }
+
+    fn as_node(&self) -> &Node;
+
+    /// Borrow the script attached to the node mutably.
+    fn with_script<T, U, V, F, R>(&self, f: F) -> Result<R>
     where
         T: GodotObject,
         U: NativeClass<Base = T, UserData = V>,
         V: UserData<Target = U> + MapMut,
-        F: FnOnce(&mut U, &T),
+        F: FnOnce(&mut U, &T) -> R,
+    {
+        let name = self.as_node().get_name().to_string();
+        let node = self.cast::<T>().ok_or_else(|| Error::Cast {
+            path: name.clone(),
+            expected: T::class_name(),
+        })?;
+
+        node.cast_instance::<U>()
+            .ok_or_else(|| Error::MissingScript(name.clone()))?
+            .map_mut(f)
+            .map_err(|_| Error::Borrow(name))
+    }
+
+    /// Instance a scene and add it as a child of `parent`,
+    /// or as a child of `self` if no parent is given.
+    fn spawn_scene<T>(&self, path: &str, parent: Option<&Node>) -> Result<Ref<T>>
+    where
+        T: GodotObject<RefKind = ManuallyManaged> + SubClass<Node>,
+    {
+        let scene = load_resource::<PackedScene>(path)?;
+
+        let instance = unsafe { scene.assume_safe() }
+            .instance(PackedScene::GEN_EDIT_STATE_DISABLED)
+            .ok_or_else(|| Error::Instance(path.to_string()))?;
+        let instance = unsafe { instance.assume_safe() };
+
+        let node = match instance.cast::<T>() {
+            Some(node) => node,
+            None => {
+                instance.free();
+                return Err(Error::Cast {
+                    path: path.to_string(),
+                    expected: T::class_name(),
+                });
+            }
+        };
+
+        parent
+            .unwrap_or_else(|| self.as_node())
+            .add_child(instance, false);
+        Ok(node.claim())
+    }
+
+    /// Move the node to a new parent.
+    /// The global transform is kept for `Node2D` and `Spatial` nodes.
+    fn reparent_keep_transform(&self, new_parent: &Node) -> Result<()> {
+        let node = self.as_node();
+        let parent = node.get_parent().ok_or(Error::NoParent)?;
+
+        let transform_2d = node.cast::<Node2D>().map(|n| n.get_global_transform());
+        let transform_3d = node.cast::<Spatial>().map(|n| n.get_global_transform());
+
+        unsafe {
+            parent.assume_safe().remove_child(node);
+        }
+        new_parent.add_child(node, false);
+
+        if let (Some(n), Some(t)) = (node.cast::<Node2D>(), transform_2d) {
+            n.set_global_transform(t);
+        }
+
+        if let (Some(n), Some(t)) = (node.cast::<Spatial>(), transform_3d) {
+            n.set_global_transform(t);
+        }
+
+        Ok(())
+    }
+
+    /// Queue all children for deletion.
+    fn free_children(&self) {
+        for child in self.as_node().get_children().iter() {
+            if let Some(child) = child.try_to_object::<Node>() {
+                unsafe { child.assume_safe() }.queue_free();
+            }
+        }
+    }
+
+    /// Queue the node for deletion after `seconds`.
+    fn queue_free_after(&self, seconds: f64) -> Result<()> {
+        let node = unsafe { self.as_node().assume_shared() };
+        call_after(self.as_node(), seconds, move || {
+            unsafe { node.assume_safe() }.queue_free();
+        })
+    }
+
+    /// Call `f` after `seconds`.
+    /// The call is cancelled if the node is freed before then.
+    fn call_after<F>(&self, seconds: f64, f: F) -> Result<()>
+    where
+        F: FnOnce() + Send + 'static,
     {
-        let node = self.cast::<T>().unwrap();
-        node.cast_instance::<U>().and_then(|val| val.map_mut(f).ok());
+        call_after(self.as_node(), seconds, f)
     }
 }
 
 macro_rules! node_ext {
     ($type: ident) => {
         impl NodeExt for $type {
-            fn get_and_cast<T: GodotObject>(&self, path: &str) -> &T {
-                let node = self.get_node(path.into()).expect("node not found");
-
-                unsafe {
-                    let n = node.assume_safe();
-                    n.cast::<T>().expect("invalid node type")
-                }
+            fn as_node(&self) -> &Node {
+                self.upcast::<Node>()
             }
         }
     };
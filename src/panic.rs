@@ -0,0 +1,210 @@
+//! Report Rust panics to Godot.
+//!
+//! Without a hook, panics are written to stderr, which is not visible
+//! in the Godot editor or in exported games.
+//!
+//! ```ignore
+//! // lib.rs
+//! fn init(handle: init::InitHandle) {
+//!     PanicHook {
+//!         crash_report: Some("user://crash.log".to_string()),
+//!         ..PanicHook::default()
+//!     }
+//!     .install();
+//! }
+//! ```
+use gdnative::api::ProjectSettings;
+use gdnative::godot_error;
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::panic::{self, Location};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// What happens after a panic is reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanicAction {
+    /// Unwind as normal
+    Continue,
+    /// Abort the process
+    Abort,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PanicHook {
+    /// Include a backtrace in the report
+    pub backtrace: bool,
+    /// Append a crash report to this file, e.g. `user://crash.log`
+    pub crash_report: Option<String>,
+    pub action: PanicAction,
+}
+
+impl Default for PanicHook {
+    fn default() -> Self {
+        Self {
+            backtrace: true,
+            crash_report: None,
+            action: PanicAction::Continue,
+        }
+    }
+}
+
+impl PanicHook {
+    /// Replace the current panic hook.
+    /// Call this from the main thread, as Godot paths are resolved here.
+    pub fn install(self) {
+        let crash_report = self.crash_report.as_ref().map(|path| {
+            PathBuf::from(
+                ProjectSettings::godot_singleton()
+                    .globalize_path(path.as_str().into())
+                    .to_string(),
+            )
+        });
+
+        let backtrace = self.backtrace;
+        let action = self.action;
+
+        panic::set_hook(Box::new(move |info| {
+            let report = report(info.payload(), info.location(), backtrace);
+            godot_error!("{}", report);
+
+            if let Some(path) = &crash_report {
+                if let Err(e) = write_crash_report(path, &report) {
+                    godot_error!("failed to write crash report {}: {}", path.display(), e);
+                }
+            }
+
+            if action == PanicAction::Abort {
+                std::process::abort();
+            }
+        }));
+        INSTALLED.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Restore the default panic hook.
+pub fn remove_panic_hook() {
+    let _ = panic::take_hook();
+    INSTALLED.store(false, Ordering::SeqCst);
+}
+
+/// True if a `PanicHook` is installed, so panics are already reported to Godot.
+pub fn is_hook_installed() -> bool {
+    INSTALLED.load(Ordering::SeqCst)
+}
+
+fn message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.as_str()
+    } else {
+        "Box<dyn Any>"
+    }
+}
+
+fn report(payload: &(dyn Any + Send), location: Option<&Location>, backtrace: bool) -> String {
+    let thread = thread::current();
+    let location = location
+        .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()))
+        .unwrap_or_else(|| "unknown location".to_string());
+
+    let mut report = format!(
+        "Rust panic in thread '{}' at {}: {}",
+        thread.name().unwrap_or("<unnamed>"),
+        location,
+        message(payload)
+    );
+
+    if backtrace {
+        report.push_str(&format!("\n{}", Backtrace::force_capture()));
+    }
+
+    report
+}
+
+fn write_crash_report(path: &Path, report: &str) -> std::io::Result<()> {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "--- crash at {} ---\n{}\n", time, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn message_from_str_and_string() {
+        assert_eq!(message(&"boom"), "boom");
+        assert_eq!(message(&"boom".to_string()), "boom");
+        assert_eq!(message(&5), "Box<dyn Any>");
+    }
+
+    #[test]
+    fn report_with_location() {
+        let location = Location::caller();
+        let report = report(&"boom", Some(location), false);
+
+        let expected = format!(
+            "at {}:{}:{}: boom",
+            location.file(),
+            location.line(),
+            location.column()
+        );
+        assert!(report.starts_with("Rust panic in thread '"), "{}", report);
+        assert!(report.ends_with(&expected), "{}", report);
+    }
+
+    #[test]
+    fn report_without_location() {
+        let report = thread::Builder::new()
+            .name("worker".to_string())
+            .spawn(|| report(&"boom".to_string(), None, false))
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(
+            report,
+            "Rust panic in thread 'worker' at unknown location: boom"
+        );
+    }
+
+    #[test]
+    fn crash_reports_are_appended() {
+        let path = std::env::temp_dir().join(format!("gdextras-crash-{}.log", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        write_crash_report(&path, "first").unwrap();
+        write_crash_report(&path, "second").unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(contents.matches("--- crash at ").count(), 2);
+        assert!(contents.find("first").unwrap() < contents.find("second").unwrap());
+    }
+
+    #[test]
+    fn install_and_remove_toggle_the_hook() {
+        // Without a crash report, installing doesn't call into the engine
+        PanicHook {
+            backtrace: false,
+            ..PanicHook::default()
+        }
+        .install();
+        assert!(is_hook_installed());
+
+        remove_panic_hook();
+        assert!(!is_hook_installed());
+    }
+}
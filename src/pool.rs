@@ -0,0 +1,526 @@
+//! Node pool
+//! Reuse nodes instead of instancing and freeing them.
+//!
+//! Nodes in the pool are kept outside of the tree, hidden and with processing
+//! disabled. `acquire` adds a node to the given parent, shows it if it was visible
+//! when created, and restores the processing it had when it entered the pool.
+//! Nodes go back into the pool with `release`, or through a `ReleaseQueue` from
+//! code that has no access to the pool, such as the node's own script.
+//! A node that is freed while in use is forgotten on the next `acquire`,
+//! and the pool can create a new one instead.
+//!
+//! ```ignore
+//! let mut bullets = NodePool::<Node2D>::from_scene("res://Bullet.tscn", 32, 256)?;
+//!
+//! if let Some(bullet) = bullets.acquire(owner.as_node()) {
+//!     unsafe { bullet.assume_safe() }.set_global_position(pos);
+//! }
+//!
+//! // Later, once the bullet hits something
+//! bullets.release(&bullet);
+//! ```
+use gdnative::api::{CanvasItem, Node, PackedScene, Spatial};
+use gdnative::{GodotObject, ManuallyManaged, Ref, SubClass};
+use std::sync::{Arc, Mutex};
+
+use crate::error::{Error, Result};
+use crate::resource::load_resource;
+
+type Factory<T> = Box<dyn Fn() -> Option<Ref<T>> + Send>;
+
+enum Source<T: GodotObject<RefKind = ManuallyManaged>> {
+    Scene(Ref<PackedScene>),
+    Factory(Factory<T>),
+}
+
+/// Pool usage
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PoolStats {
+    /// Nodes waiting in the pool
+    pub available: usize,
+    /// Nodes currently handed out
+    pub in_use: usize,
+    /// Max number of nodes the pool will create
+    pub capacity: usize,
+    /// Number of nodes created so far
+    pub created: usize,
+    /// Number of times a pooled node was handed out again
+    pub reused: usize,
+    /// Number of times `acquire` failed because the pool was at capacity
+    pub exhausted: usize,
+}
+
+/// Release nodes without access to the pool, e.g. from the node's script
+/// once it is done. Released nodes go back into the pool on the next
+/// `acquire` or `reclaim`. Clones share the same queue.
+#[derive(Debug, Clone, Default)]
+pub struct ReleaseQueue {
+    ids: Arc<Mutex<Vec<i64>>>,
+}
+
+impl ReleaseQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn release(&self, node: &Node) {
+        self.ids
+            .lock()
+            .expect("release queue poisoned")
+            .push(node.get_instance_id());
+    }
+
+    fn take(&self) -> Vec<i64> {
+        std::mem::take(&mut *self.ids.lock().expect("release queue poisoned"))
+    }
+}
+
+pub struct NodePool<T>
+where
+    T: GodotObject<RefKind = ManuallyManaged> + SubClass<Node>,
+{
+    source: Source<T>,
+    slots: Slots<Pooled<T>>,
+    released: ReleaseQueue,
+}
+
+struct Pooled<T: GodotObject<RefKind = ManuallyManaged>> {
+    id: i64,
+    node: Ref<T>,
+    /// Visibility when the node was created
+    visible: bool,
+    /// Processing when the node entered the pool.
+    /// `None` until the node has been in the tree, as `_ready` sets up processing.
+    processing: Option<Processing>,
+}
+
+impl<T> NodePool<T>
+where
+    T: GodotObject<RefKind = ManuallyManaged> + SubClass<Node>,
+{
+    /// Create a pool from a scene, instancing `preload` nodes up front.
+    /// The pool grows on demand up to `capacity` nodes.
+    pub fn from_scene(path: &str, preload: usize, capacity: usize) -> Result<Self> {
+        let scene = load_resource::<PackedScene>(path)?;
+
+        let mut pool = Self::with_source(Source::Scene(scene), capacity);
+        pool.preload(preload);
+
+        if pool.slots.stats.created < preload.min(capacity) {
+            return Err(Error::Instance(path.to_string()));
+        }
+
+        Ok(pool)
+    }
+
+    /// Create a pool where new nodes are created by `factory`.
+    pub fn from_factory<F>(factory: F, preload: usize, capacity: usize) -> Self
+    where
+        F: Fn() -> Option<Ref<T>> + Send + 'static,
+    {
+        let mut pool = Self::with_source(Source::Factory(Box::new(factory)), capacity);
+        pool.preload(preload);
+        pool
+    }
+
+    fn with_source(source: Source<T>, capacity: usize) -> Self {
+        Self {
+            source,
+            slots: Slots::new(capacity),
+            released: ReleaseQueue::new(),
+        }
+    }
+
+    /// Use a queue created before the pool, e.g. one captured by the factory.
+    pub fn with_release_queue(mut self, queue: ReleaseQueue) -> Self {
+        self.released = queue;
+        self
+    }
+
+    /// A queue to release nodes of this pool from elsewhere.
+    pub fn release_queue(&self) -> ReleaseQueue {
+        self.released.clone()
+    }
+
+    fn preload(&mut self, count: usize) {
+        let source = &self.source;
+        self.slots.preload(count, || create(source));
+    }
+
+    /// Hand out a node, adding it as a child of `parent`.
+    /// Returns `None` if the pool is empty and at capacity.
+    pub fn acquire(&mut self, parent: &Node) -> Option<Ref<T>> {
+        self.reclaim();
+
+        let source = &self.source;
+        let pooled = self.slots.acquire(|| create(source))?;
+
+        let node = pooled.node.clone();
+        let n = unsafe { node.assume_safe() };
+        parent.add_child(n, false);
+        set_visible(n.upcast(), pooled.visible);
+        if let Some(processing) = pooled.processing {
+            processing.apply(n.upcast());
+        }
+
+        Some(node)
+    }
+
+    /// Return a node to the pool.
+    /// The node is removed from its parent, hidden and has processing disabled.
+    /// Returns false if the node was not handed out by this pool.
+    pub fn release(&mut self, node: &Ref<T>) -> bool {
+        self.release_id(instance_id(node))
+    }
+
+    fn release_id(&mut self, id: i64) -> bool {
+        match self.slots.release(|p| p.id == id) {
+            Some(pooled) => {
+                deactivate(pooled);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Return nodes released through the `ReleaseQueue`, and forget nodes
+    /// that were freed or queued for deletion while in use,
+    /// so the pool can create new nodes in their place.
+    pub fn reclaim(&mut self) {
+        self.slots.forget(|pooled| {
+            !unsafe { pooled.node.is_instance_sane() }
+                || unsafe { pooled.node.assume_safe() }
+                    .upcast::<Node>()
+                    .is_queued_for_deletion()
+        });
+
+        for id in self.released.take() {
+            self.release_id(id);
+        }
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        self.slots.stats()
+    }
+
+    /// Free all nodes waiting in the pool.
+    /// Nodes that are in use are left alone.
+    pub fn clear(&mut self) {
+        for pooled in self.slots.drain_available() {
+            unsafe { pooled.node.assume_safe() }.upcast::<Node>().free();
+        }
+    }
+}
+
+impl<T> Drop for NodePool<T>
+where
+    T: GodotObject<RefKind = ManuallyManaged> + SubClass<Node>,
+{
+    fn drop(&mut self) {
+        // Pooled nodes are outside of the tree and would otherwise leak
+        for pooled in self.slots.drain_available() {
+            unsafe { pooled.node.assume_safe() }.upcast::<Node>().free();
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Slots -
+// -----------------------------------------------------------------------------
+/// Bookkeeping of available and handed out items, independent of Godot.
+struct Slots<N> {
+    available: Vec<N>,
+    in_use: Vec<N>,
+    stats: PoolStats,
+}
+
+impl<N> Slots<N> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            available: Vec::new(),
+            in_use: Vec::new(),
+            stats: PoolStats {
+                capacity,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Create up to `count` available items, without going over capacity.
+    fn preload(&mut self, count: usize, mut create: impl FnMut() -> Option<N>) {
+        for _ in 0..count.min(self.stats.capacity) {
+            match self.create(&mut create) {
+                Some(item) => self.available.push(item),
+                None => break,
+            }
+        }
+    }
+
+    fn create(&mut self, create: impl FnOnce() -> Option<N>) -> Option<N> {
+        if self.stats.created >= self.stats.capacity {
+            return None;
+        }
+
+        let item = create()?;
+        self.stats.created += 1;
+        Some(item)
+    }
+
+    /// Hand out an available item, or create a new one.
+    fn acquire(&mut self, create: impl FnOnce() -> Option<N>) -> Option<&mut N> {
+        let item = match self.available.pop() {
+            Some(item) => {
+                self.stats.reused += 1;
+                item
+            }
+            None => match self.create(create) {
+                Some(item) => item,
+                None => {
+                    self.stats.exhausted += 1;
+                    return None;
+                }
+            },
+        };
+
+        self.in_use.push(item);
+        self.in_use.last_mut()
+    }
+
+    /// Make the first handed out item matching `is_item` available again.
+    fn release(&mut self, is_item: impl Fn(&N) -> bool) -> Option<&mut N> {
+        let index = self.in_use.iter().position(is_item)?;
+        let item = self.in_use.swap_remove(index);
+        self.available.push(item);
+        self.available.last_mut()
+    }
+
+    /// Drop handed out items that are gone, freeing up capacity.
+    fn forget(&mut self, is_gone: impl Fn(&N) -> bool) {
+        let len = self.in_use.len();
+        self.in_use.retain(|item| !is_gone(item));
+        self.stats.created -= len - self.in_use.len();
+    }
+
+    /// Take out all available items, freeing up capacity.
+    fn drain_available(&mut self) -> std::vec::Drain<'_, N> {
+        self.stats.created -= self.available.len();
+        self.available.drain(..)
+    }
+
+    fn stats(&self) -> PoolStats {
+        PoolStats {
+            available: self.available.len(),
+            in_use: self.in_use.len(),
+            ..self.stats
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Nodes -
+// -----------------------------------------------------------------------------
+fn create<T>(source: &Source<T>) -> Option<Pooled<T>>
+where
+    T: GodotObject<RefKind = ManuallyManaged> + SubClass<Node>,
+{
+    let node = match source {
+        Source::Scene(scene) => {
+            let instance =
+                unsafe { scene.assume_safe() }.instance(PackedScene::GEN_EDIT_STATE_DISABLED)?;
+            let instance = unsafe { instance.assume_safe() };
+            match instance.cast::<T>() {
+                Some(node) => node.claim(),
+                None => {
+                    instance.free();
+                    return None;
+                }
+            }
+        }
+        Source::Factory(factory) => factory()?,
+    };
+
+    let n = unsafe { node.assume_safe() }.upcast::<Node>();
+    let visible = is_visible(n);
+    set_visible(n, false);
+
+    Some(Pooled {
+        id: n.get_instance_id(),
+        node,
+        visible,
+        processing: None,
+    })
+}
+
+fn instance_id<T>(node: &Ref<T>) -> i64
+where
+    T: GodotObject<RefKind = ManuallyManaged> + SubClass<Node>,
+{
+    unsafe { node.assume_safe() }
+        .upcast::<Node>()
+        .get_instance_id()
+}
+
+/// Remove a node from the tree, hide it and disable its processing,
+/// recording the processing to restore on `acquire`.
+fn deactivate<T>(pooled: &mut Pooled<T>)
+where
+    T: GodotObject<RefKind = ManuallyManaged> + SubClass<Node>,
+{
+    let node = unsafe { pooled.node.assume_safe() }.upcast::<Node>();
+    pooled.processing = Some(Processing::read(node));
+
+    if let Some(parent) = node.get_parent() {
+        unsafe { parent.assume_safe() }.remove_child(node);
+    }
+
+    Processing::NONE.apply(node);
+    set_visible(node, false);
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Processing {
+    process: bool,
+    physics_process: bool,
+    input: bool,
+    unhandled_input: bool,
+}
+
+impl Processing {
+    const NONE: Processing = Processing {
+        process: false,
+        physics_process: false,
+        input: false,
+        unhandled_input: false,
+    };
+
+    fn read(node: &Node) -> Self {
+        Self {
+            process: node.is_processing(),
+            physics_process: node.is_physics_processing(),
+            input: node.is_processing_input(),
+            unhandled_input: node.is_processing_unhandled_input(),
+        }
+    }
+
+    fn apply(self, node: &Node) {
+        node.set_process(self.process);
+        node.set_physics_process(self.physics_process);
+        node.set_process_input(self.input);
+        node.set_process_unhandled_input(self.unhandled_input);
+    }
+}
+
+fn is_visible(node: &Node) -> bool {
+    match (node.cast::<CanvasItem>(), node.cast::<Spatial>()) {
+        (Some(item), _) => item.is_visible(),
+        (_, Some(spatial)) => spatial.is_visible(),
+        _ => true,
+    }
+}
+
+fn set_visible(node: &Node, visible: bool) {
+    if let Some(item) = node.cast::<CanvasItem>() {
+        item.set_visible(visible);
+    }
+
+    if let Some(spatial) = node.cast::<Spatial>() {
+        spatial.set_visible(visible);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counter() -> impl FnMut() -> Option<u32> {
+        let mut next = 0;
+        move || {
+            next += 1;
+            Some(next)
+        }
+    }
+
+    #[test]
+    fn preload_is_limited_by_capacity() {
+        let mut slots = Slots::new(3);
+        slots.preload(5, counter());
+
+        let stats = slots.stats();
+        assert_eq!(stats.available, 3);
+        assert_eq!(stats.created, 3);
+        assert_eq!(stats.in_use, 0);
+    }
+
+    #[test]
+    fn preload_stops_when_create_fails() {
+        let mut slots: Slots<u32> = Slots::new(8);
+        let mut create = counter();
+        slots.preload(5, || create().filter(|n| *n < 3));
+
+        assert_eq!(slots.stats().available, 2);
+        assert_eq!(slots.stats().created, 2);
+    }
+
+    #[test]
+    fn acquire_reuses_then_creates_then_is_exhausted() {
+        let mut slots = Slots::new(2);
+        let mut create = counter();
+        slots.preload(1, &mut create);
+
+        assert_eq!(slots.acquire(&mut create).copied(), Some(1));
+        assert_eq!(slots.acquire(&mut create).copied(), Some(2));
+        assert_eq!(slots.acquire(&mut create), None);
+
+        let stats = slots.stats();
+        assert_eq!(stats.in_use, 2);
+        assert_eq!(stats.created, 2);
+        assert_eq!(stats.reused, 1);
+        assert_eq!(stats.exhausted, 1);
+    }
+
+    #[test]
+    fn release_makes_item_available() {
+        let mut slots = Slots::new(2);
+        let mut create = counter();
+        slots.acquire(&mut create);
+        slots.acquire(&mut create);
+
+        assert_eq!(slots.release(|n| *n == 1).copied(), Some(1));
+        assert_eq!(slots.release(|n| *n == 1), None);
+        assert_eq!(slots.release(|n| *n == 7), None);
+
+        assert_eq!(slots.stats().available, 1);
+        assert_eq!(slots.stats().in_use, 1);
+        assert_eq!(slots.acquire(&mut create).copied(), Some(1));
+        assert_eq!(slots.stats().reused, 1);
+    }
+
+    #[test]
+    fn forget_frees_capacity() {
+        let mut slots = Slots::new(2);
+        let mut create = counter();
+        slots.acquire(&mut create);
+        slots.acquire(&mut create);
+
+        slots.forget(|n| *n == 2);
+        let stats = slots.stats();
+        assert_eq!(stats.in_use, 1);
+        assert_eq!(stats.created, 1);
+
+        assert_eq!(slots.acquire(&mut create).copied(), Some(3));
+    }
+
+    #[test]
+    fn drain_available_frees_capacity() {
+        let mut slots = Slots::new(3);
+        let mut create = counter();
+        slots.preload(3, &mut create);
+        slots.acquire(&mut create);
+
+        assert_eq!(slots.drain_available().count(), 2);
+        let stats = slots.stats();
+        assert_eq!(stats.available, 0);
+        assert_eq!(stats.in_use, 1);
+        assert_eq!(stats.created, 1);
+    }
+}
@@ -0,0 +1,164 @@
+//! Typed resource loading and caching.
+//!
+//! ```ignore
+//! let texture = load_resource::<Texture>("res://icon.png")?;
+//!
+//! let mut cache = ResourceCache::new();
+//! if let Err(errors) = cache.preload(&["res://Bullet.tscn", "res://sfx/boink.wav"]) {
+//!     errors.iter().for_each(|e| gd_err!("{}", e));
+//! }
+//! let scene = cache.get::<PackedScene>("res://Bullet.tscn")?;
+//! ```
+use gdnative::api::{Resource, ResourceLoader};
+use gdnative::{GodotObject, Ref, RefCounted, SubClass};
+use std::collections::HashMap;
+
+use crate::error::{Error, Result};
+
+// -----------------------------------------------------------------------------
+//     - Loading -
+// -----------------------------------------------------------------------------
+/// Load a resource and cast it to `T`.
+/// E.g
+/// ```ignore
+/// let stream = load_resource::<AudioStream>("res://sfx/ping.wav")?;
+/// ```
+pub fn load_resource<T>(path: &str) -> Result<Ref<T>>
+where
+    T: GodotObject<RefKind = RefCounted> + SubClass<Resource>,
+{
+    load_untyped(path, T::class_name())?
+        .cast::<T>()
+        .ok_or_else(|| Error::Cast {
+            path: path.to_string(),
+            expected: T::class_name(),
+        })
+}
+
+pub(crate) fn load_untyped(path: &str, type_hint: &str) -> Result<Ref<Resource>> {
+    ResourceLoader::godot_singleton()
+        .load(path.into(), type_hint.into(), false)
+        .ok_or_else(|| Error::Load(path.to_string()))
+}
+
+// -----------------------------------------------------------------------------
+//     - Cache -
+// -----------------------------------------------------------------------------
+struct Entry {
+    resource: Ref<Resource>,
+    refs: usize,
+    pinned: bool,
+}
+
+/// Resources keyed by path.
+///
+/// Every `get` increases the reference count of a path and every `release`
+/// decreases it. `evict_unused` drops resources that are no longer referenced
+/// and were not preloaded.
+#[derive(Default)]
+pub struct ResourceCache {
+    entries: HashMap<String, Entry>,
+}
+
+impl ResourceCache {
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Load and pin a list of resources.
+    /// Pinned resources are kept until explicitly evicted.
+    /// All paths are attempted, and every failure is returned.
+    pub fn preload(&mut self, paths: &[&str]) -> Result<(), Vec<Error>> {
+        let mut errors = Vec::new();
+
+        for path in paths {
+            match self.entry(path, "") {
+                Ok(entry) => entry.pinned = true,
+                Err(e) => errors.push(e),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Get a resource, loading it if it's not cached.
+    pub fn get<T>(&mut self, path: &str) -> Result<Ref<T>>
+    where
+        T: GodotObject<RefKind = RefCounted> + SubClass<Resource>,
+    {
+        let entry = self.entry(path, T::class_name())?;
+        let resource = entry
+            .resource
+            .clone()
+            .cast::<T>()
+            .ok_or_else(|| Error::Cast {
+                path: path.to_string(),
+                expected: T::class_name(),
+            })?;
+
+        entry.refs += 1;
+        Ok(resource)
+    }
+
+    fn entry(&mut self, path: &str, type_hint: &str) -> Result<&mut Entry> {
+        if !self.entries.contains_key(path) {
+            let resource = load_untyped(path, type_hint)?;
+            self.entries.insert(
+                path.to_string(),
+                Entry {
+                    resource,
+                    refs: 0,
+                    pinned: false,
+                },
+            );
+        }
+
+        Ok(self.entries.get_mut(path).expect("entry was just inserted"))
+    }
+
+    /// Release a reference acquired with `get`.
+    pub fn release(&mut self, path: &str) {
+        if let Some(entry) = self.entries.get_mut(path) {
+            entry.refs = entry.refs.saturating_sub(1);
+        }
+    }
+
+    pub fn ref_count(&self, path: &str) -> usize {
+        self.entries.get(path).map(|e| e.refs).unwrap_or(0)
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.entries.contains_key(path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Remove a resource from the cache, even if it's still referenced.
+    pub fn evict(&mut self, path: &str) -> bool {
+        self.entries.remove(path).is_some()
+    }
+
+    /// Remove all resources that are neither referenced nor pinned.
+    /// Returns the number of evicted resources.
+    pub fn evict_unused(&mut self) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|_, e| e.refs > 0 || e.pinned);
+        before - self.entries.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
@@ -1,137 +1,793 @@
 //! # Scene loader
 //!
-//! This requires the `SceneLoader` to be added to the root node, 
-//! and named "SceneLoader".
-//! This is still a work in progress.
+//! This requires the `SceneLoader` to be added as an autoload,
+//! named "SceneLoader".
 //!
 //! ```ignore
-//! with_scene_loader!( owner, |loader, node| {
-//!     loader.change_scene(node, "res://World.tscn");
-//! });
+//! SceneLoader::with_autoload(owner, |loader, node| {
+//!     loader.change_scene(&node, "res://World.tscn")
+//! })??;
 //! ```
+//!
+//! ## Signals
+//!
+//! * `progress(current, total)`: emitted for every loaded stage
+//! * `scene_changed(path)`: emitted once the new scene is in the tree
+//! * `scene_change_failed(path, error)`: emitted when the new scene fails to load.
+//!   If the scene can't be opened at all, the scene change returns the error
+//!   and the signal is emitted before any transition starts.
+//!   A pushed scene resumes the suspended scene. When replacing a scene, the old
+//!   scene is only kept if the load fails during `Phase::Out`, or if the old scene
+//!   is kept alive by `transition/keep_old_scene`. Otherwise there is no current
+//!   scene, and the handler should change to a fallback scene.
+//!
+//! ## Loading screen
+//!
+//! Set `loading/screen` to the path of a scene to show it while loading.
+//! The loading screen is shown for at least `loading/min_display_time` seconds.
+//!
+//! ## Transitions
+//!
+//! A `Transition` can be set with `set_transition` to run an effect before and
+//! after the scene swap (see the `transition` module).
+//! Set `transition/keep_old_scene` to keep the old scene alive and visible until
+//! the new scene is ready. The loading screen is not shown in that case.
+//!
+//! ## Scene stack
+//!
+//! `push_scene` suspends the current scene and loads a new scene on top of it,
+//! `pop_scene` frees the top scene and resumes the one below it.
+//! A suspended scene either stays in the tree with processing disabled
+//! (`PushMode::Overlay`, e.g. a pause menu over gameplay),
+//! or is removed from the tree and kept alive (`PushMode::Detach`).
+//!
+//! ## Payload
+//!
+//! A payload can be passed to the incoming scene with `change_scene_with` or
+//! `push_scene_with`, and retrieved by the scene's script (e.g. in `_ready`)
+//! with `take_payload`:
+//!
+//! ```ignore
+//! // Outgoing scene
+//! SceneLoader::with_autoload(owner, |loader, node| {
+//!     loader.change_scene_with(&node, "res://Level.tscn", LevelInfo { number: 2 })
+//! })??;
+//!
+//! // Incoming scene
+//! if let Some(info) = take_payload::<LevelInfo>() {
+//!     self.level = info.number;
+//! }
+//! ```
+use crate::error::{Error, Result};
+use crate::impl_autoload;
+use crate::resource::load_resource;
+use crate::thread::ThreadBound;
+use crate::transition::{Phase, Transition, TransitionContext};
 use crate::{gd_err, some_or_bail};
-use gdnative::*;
-
-#[macro_export]
-macro_rules! with_scene_loader {
-    ($node: ident, $e: expr) => {
-        unsafe {
-            match $node.get_node("/root/SceneLoader".into()) {
-                Some(wd) => {
-                    let n = wd.cast::<Node>().unwrap();
-                    let _ = Instance::<SceneLoader>::try_from_base(n)
-                        .unwrap()
-                        .map_mut($e);
-                }
-                None => (),
-            }
-        }
-    };
-}
-
+use gdnative::api::{
+    CanvasLayer, ColorRect, Control, Node, PackedScene, Resource, ResourceInteractiveLoader,
+    ResourceLoader,
+};
+use gdnative::init::property::{ExportInfo, Usage};
+use gdnative::init::{ClassBuilder, Signal, SignalArgument};
+use gdnative::{
+    methods, Color, GodotError, GodotObject, NativeClass, Null, Ref, ToVariant, Variant,
+    VariantType,
+};
+use std::any::Any;
+use std::sync::Mutex;
 
+/// Interactive scene loader.
+/// The loader can only be used on the thread that created it.
 pub struct Loader {
-    inner: ResourceInteractiveLoader,
+    inner: ThreadBound<Ref<ResourceInteractiveLoader>>,
 }
 
 impl Loader {
-    pub fn new(path: &str) -> Option<Self> {
-        let mut loader = ResourceLoader::godot_singleton();
+    pub fn new(path: &str) -> Result<Self> {
+        let loader = ResourceLoader::godot_singleton();
+
+        let inner = loader
+            .load_interactive(path.into(), "PackedScene".into())
+            .ok_or_else(|| Error::Load(path.to_string()))?;
 
-        Some(Self {
-            inner: loader.load_interactive(path.into(), "PackedScene".into())?,
+        Ok(Self {
+            inner: ThreadBound::new(inner),
         })
     }
 
-    pub fn poll(&mut self) -> Result<(), gdnative::GodotError> {
-        self.inner.poll()
+    fn loader(&self) -> &ResourceInteractiveLoader {
+        unsafe { self.inner.assume_safe() }.as_ref()
+    }
+
+    /// Load the next stage.
+    /// Returns `Error::Godot(GodotError::FileEof)` once the scene is loaded.
+    pub fn poll(&mut self) -> Result<()> {
+        Ok(self.loader().poll()?)
     }
 
     pub fn get_stage(&self) -> i64 {
-        self.inner.get_stage()
+        self.loader().get_stage()
     }
 
     pub fn get_stage_count(&self) -> i64 {
-        self.inner.get_stage_count()
+        self.loader().get_stage_count()
     }
 
-    pub fn get_resource(&mut self) -> Option<Resource> {
-        self.inner.get_resource()
+    pub fn get_resource(&mut self) -> Option<Ref<Resource>> {
+        self.loader().get_resource()
     }
 }
 
-impl Drop for Loader {
-    fn drop(&mut self) {
-        unsafe {
-            self.inner.free();
+// -----------------------------------------------------------------------------
+//     - Payload -
+// -----------------------------------------------------------------------------
+// The incoming scene is added while the scene loader is borrowed,
+// so the payload is kept outside of the loader.
+static PAYLOAD: Mutex<Option<Box<dyn Any + Send>>> = Mutex::new(None);
+
+fn set_payload(payload: Option<Box<dyn Any + Send>>) {
+    *PAYLOAD.lock().expect("scene payload poisoned") = payload;
+}
+
+/// Take the payload passed to the current scene change.
+/// Returns `None` if there is no payload, or if it is not of type `P`
+/// (in which case the payload is left in place).
+pub fn take_payload<P: Any>() -> Option<P> {
+    let mut payload = PAYLOAD.lock().expect("scene payload poisoned");
+    match payload.take()?.downcast::<P>() {
+        Ok(p) => Some(*p),
+        Err(other) => {
+            *payload = Some(other);
+            None
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Scene stack -
+// -----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PushMode {
+    /// Keep the scene in the tree, with processing disabled
+    Overlay,
+    /// Remove the scene from the tree, keeping it alive
+    Detach,
+}
+
+struct ProcessState {
+    node: Ref<Node>,
+    process: bool,
+    physics_process: bool,
+    input: bool,
+    unhandled_input: bool,
+}
+
+struct SuspendedScene {
+    path: String,
+    node: Ref<Node>,
+    mode: PushMode,
+    process_states: Vec<ProcessState>,
+}
+
+/// Disable processing on a node and all of its children,
+/// storing the previous state so it can be restored.
+fn suspend_processing(node: &Node, states: &mut Vec<ProcessState>) {
+    states.push(ProcessState {
+        node: unsafe { node.assume_shared() },
+        process: node.is_processing(),
+        physics_process: node.is_physics_processing(),
+        input: node.is_processing_input(),
+        unhandled_input: node.is_processing_unhandled_input(),
+    });
+
+    node.set_process(false);
+    node.set_physics_process(false);
+    node.set_process_input(false);
+    node.set_process_unhandled_input(false);
+
+    for child in node.get_children().iter() {
+        if let Some(child) = child.try_to_object::<Node>() {
+            suspend_processing(unsafe { child.assume_safe() }.as_ref(), states);
         }
     }
 }
 
-unsafe impl Send for Loader {}
+fn resume_processing(states: Vec<ProcessState>) {
+    for state in states {
+        if !unsafe { state.node.is_instance_sane() } {
+            continue;
+        }
+
+        let node = unsafe { state.node.assume_safe() };
+        node.set_process(state.process);
+        node.set_physics_process(state.physics_process);
+        node.set_process_input(state.input);
+        node.set_process_unhandled_input(state.unhandled_input);
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Scene loader -
+// -----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Idle,
+    /// Running `Phase::Out`, with the time spent in the phase
+    Out(f64),
+    /// Waiting for the new scene and the loading screen
+    Loading,
+    /// Running `Phase::In`, with the time spent in the phase
+    In(f64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+    Replace,
+    Push(PushMode),
+    Pop,
+}
 
 #[derive(NativeClass)]
 #[inherit(Node)]
+#[register_with(Self::register)]
 pub struct SceneLoader {
+    #[property(path = "loading/screen")]
+    loading_screen: String,
+    #[property(path = "loading/min_display_time", default = 0.0)]
+    min_display_time: f64,
+    #[property(path = "transition/keep_old_scene", default = false)]
+    keep_old_scene: bool,
     loader: Option<Loader>,
+    path: String,
+    elapsed: f64,
+    state: State,
+    change: Change,
+    transition: Option<Box<dyn Transition>>,
+    overlay: Option<Ref<ColorRect>>,
+    pending_scene: Option<Ref<PackedScene>>,
+    current_scene: Option<Ref<Node>>,
+    current_path: String,
+    stack: Vec<SuspendedScene>,
+    old_scene: Option<Ref<Node>>,
+    new_scene: Option<Ref<Node>>,
+    loading_screen_node: Option<Ref<Node>>,
 }
 
+impl_autoload!(SceneLoader, "SceneLoader");
+
 #[methods]
 impl SceneLoader {
-    pub fn _init(_owner: Node) -> Self {
-        Self { loader: None }
+    fn new(_owner: &Node) -> Self {
+        Self {
+            loading_screen: String::new(),
+            min_display_time: 0.0,
+            keep_old_scene: false,
+            loader: None,
+            path: String::new(),
+            elapsed: 0.0,
+            state: State::Idle,
+            change: Change::Replace,
+            transition: None,
+            overlay: None,
+            pending_scene: None,
+            current_scene: None,
+            current_path: String::new(),
+            stack: Vec::new(),
+            old_scene: None,
+            new_scene: None,
+            loading_screen_node: None,
+        }
     }
 
-    pub fn change_scene(&mut self, mut owner: Node, path: &str) {
-        self.loader = Loader::new(path);
+    fn register(builder: &ClassBuilder<Self>) {
+        builder.add_signal(Signal {
+            name: "progress",
+            args: &[
+                SignalArgument {
+                    name: "current",
+                    default: Variant::from_i64(0),
+                    export_info: ExportInfo::new(VariantType::I64),
+                    usage: Usage::DEFAULT,
+                },
+                SignalArgument {
+                    name: "total",
+                    default: Variant::from_i64(0),
+                    export_info: ExportInfo::new(VariantType::I64),
+                    usage: Usage::DEFAULT,
+                },
+            ],
+        });
 
-        unsafe {
-            owner.set_process(true);
+        builder.add_signal(Signal {
+            name: "scene_changed",
+            args: &[SignalArgument {
+                name: "path",
+                default: Variant::new(),
+                export_info: ExportInfo::new(VariantType::GodotString),
+                usage: Usage::DEFAULT,
+            }],
+        });
 
-            let tree = some_or_bail!(owner.get_tree(), "failed to get scene tree");
-            let root = some_or_bail!(tree.get_root(), "failed to get root node");
-            let mut current_scene = some_or_bail!(
-                root.get_child(root.get_child_count() - 1),
-                "failed to get current scene"
-            );
+        builder.add_signal(Signal {
+            name: "scene_change_failed",
+            args: &[
+                SignalArgument {
+                    name: "path",
+                    default: Variant::new(),
+                    export_info: ExportInfo::new(VariantType::GodotString),
+                    usage: Usage::DEFAULT,
+                },
+                SignalArgument {
+                    name: "error",
+                    default: Variant::new(),
+                    export_info: ExportInfo::new(VariantType::GodotString),
+                    usage: Usage::DEFAULT,
+                },
+            ],
+        });
+    }
 
-            current_scene.queue_free();
+    /// Set the transition used by the following scene changes.
+    /// `None` swaps scenes without any effect.
+    pub fn set_transition(&mut self, transition: Option<Box<dyn Transition>>) {
+        self.transition = transition;
+    }
+
+    /// The scene currently on top of the stack.
+    /// Until the first scene change this is the main scene of the tree.
+    pub fn current_scene(&self, owner: &Node) -> Option<Ref<Node>> {
+        if let Some(scene) = &self.current_scene {
+            return Some(scene.clone());
         }
+
+        let tree = owner.get_tree()?;
+        unsafe { tree.assume_safe() }.get_current_scene()
+    }
+
+    /// Number of suspended scenes below the current scene.
+    pub fn stack_depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Replace the current scene.
+    pub fn change_scene(&mut self, owner: &Node, path: &str) -> Result<()> {
+        self.begin(owner, path, Change::Replace, None)
+    }
+
+    /// Replace the current scene, passing a payload to the new scene.
+    pub fn change_scene_with<P: Any + Send>(
+        &mut self,
+        owner: &Node,
+        path: &str,
+        payload: P,
+    ) -> Result<()> {
+        self.begin(owner, path, Change::Replace, Some(Box::new(payload)))
+    }
+
+    /// Suspend the current scene and load a new scene on top of it.
+    pub fn push_scene(&mut self, owner: &Node, path: &str, mode: PushMode) -> Result<()> {
+        self.begin(owner, path, Change::Push(mode), None)
+    }
+
+    /// Suspend the current scene and load a new scene on top of it,
+    /// passing a payload to the new scene.
+    pub fn push_scene_with<P: Any + Send>(
+        &mut self,
+        owner: &Node,
+        path: &str,
+        mode: PushMode,
+        payload: P,
+    ) -> Result<()> {
+        self.begin(owner, path, Change::Push(mode), Some(Box::new(payload)))
+    }
+
+    /// Free the current scene and resume the scene below it.
+    /// Returns false if there is no scene to return to.
+    pub fn pop_scene(&mut self, owner: &Node) -> bool {
+        if self.state != State::Idle {
+            gd_err!("already changing scene to {}", self.path);
+            return false;
+        }
+
+        if self.stack.is_empty() {
+            gd_err!("no scene to pop back to");
+            return false;
+        }
+
+        self.change = Change::Pop;
+        self.old_scene = self.current_scene(owner);
+        self.start_phase(Phase::Out);
+        self.state = State::Out(0.0);
+        owner.set_process(true);
+        true
+    }
+
+    fn begin(
+        &mut self,
+        owner: &Node,
+        path: &str,
+        change: Change,
+        payload: Option<Box<dyn Any + Send>>,
+    ) -> Result<()> {
+        if self.state != State::Idle {
+            return Err(Error::SceneChangeInProgress(self.path.clone()));
+        }
+
+        let loader = match Loader::new(path) {
+            Ok(loader) => loader,
+            Err(e) => {
+                self.state = State::Idle;
+                owner.set_process(false);
+                owner.emit_signal(
+                    "scene_change_failed".into(),
+                    &[path.to_variant(), e.to_string().to_variant()],
+                );
+                return Err(e);
+            }
+        };
+
+        self.loader = Some(loader);
+        set_payload(payload);
+        self.path = path.to_string();
+        self.change = change;
+        self.elapsed = 0.0;
+        self.pending_scene = None;
+        self.old_scene = self.current_scene(owner);
+
+        self.start_phase(Phase::Out);
+        self.state = State::Out(0.0);
+        owner.set_process(true);
+        Ok(())
+    }
+
+    fn keeps_old_scene(&self) -> bool {
+        self.keep_old_scene
+            || self
+                .transition
+                .as_ref()
+                .map(|t| t.keep_old_scene())
+                .unwrap_or(false)
+    }
+
+    fn show_loading_screen(&mut self, owner: &Node) {
+        if self.loading_screen.is_empty() {
+            return;
+        }
+
+        let scene = some_or_bail!(
+            load_resource::<PackedScene>(&self.loading_screen).ok(),
+            "failed to load loading screen: {}",
+            self.loading_screen
+        );
+        let node = some_or_bail!(
+            unsafe { scene.assume_safe() }.instance(PackedScene::GEN_EDIT_STATE_DISABLED),
+            "failed to instance loading screen"
+        );
+
+        let tree = some_or_bail!(owner.get_tree(), "failed to get scene tree");
+        let root = some_or_bail!(
+            unsafe { tree.assume_safe() }.get_root(),
+            "failed to get root node"
+        );
+        unsafe { root.assume_safe() }.add_child(node.clone(), true);
+
+        self.loading_screen_node = Some(node);
+
+        // The overlay would cover the loading screen
+        if let Some(overlay) = &self.overlay {
+            unsafe { overlay.assume_safe() }.set_visible(false);
+        }
+    }
+
+    fn hide_loading_screen(&mut self) {
+        if let Some(node) = self.loading_screen_node.take() {
+            unsafe { node.assume_safe() }.queue_free();
+        }
+    }
+
+    fn free_old_scene(&mut self) {
+        if let Some(node) = self.old_scene.take() {
+            unsafe { node.assume_safe() }.queue_free();
+        }
+    }
+
+    /// Move the old scene onto the stack.
+    fn suspend_old_scene(&mut self, mode: PushMode) {
+        let node = some_or_bail!(self.old_scene.take(), "no scene to suspend");
+        let n = unsafe { node.assume_safe() };
+        let mut process_states = Vec::new();
+
+        match mode {
+            PushMode::Overlay => suspend_processing(n.as_ref(), &mut process_states),
+            PushMode::Detach => {
+                if let Some(parent) = n.get_parent() {
+                    unsafe { parent.assume_safe() }.remove_child(n);
+                }
+            }
+        }
+
+        self.stack.push(SuspendedScene {
+            path: self.current_path.clone(),
+            node,
+            mode,
+            process_states,
+        });
+        self.current_scene = None;
+    }
+
+    /// Resume the scene on top of the stack.
+    fn resume_scene(&mut self, owner: &Node) {
+        let scene = some_or_bail!(self.stack.pop(), "no scene to resume");
+
+        let tree = some_or_bail!(owner.get_tree(), "failed to get scene tree");
+        let tree = unsafe { tree.assume_safe() };
+
+        match scene.mode {
+            PushMode::Overlay => resume_processing(scene.process_states),
+            PushMode::Detach => {
+                let root = some_or_bail!(tree.get_root(), "failed to get root node");
+                unsafe { root.assume_safe() }.add_child(scene.node.clone(), true);
+            }
+        }
+
+        tree.set_current_scene(scene.node.clone());
+        self.path = scene.path.clone();
+        self.set_current(scene.node, scene.path);
+
+        owner.emit_signal("scene_changed".into(), &[self.path.to_variant()]);
+
+        self.start_phase(Phase::In);
+        self.state = State::In(0.0);
+    }
+
+    fn set_current(&mut self, node: Ref<Node>, path: String) {
+        self.current_scene = Some(node.clone());
+        self.current_path = path;
+        self.new_scene = Some(node);
     }
 
     #[export]
-    pub fn _ready(&self, mut owner: Node) {
-        unsafe { owner.set_process(false) }
+    fn _ready(&mut self, owner: &Node) {
+        owner.set_process(false);
+
+        let layer = CanvasLayer::new();
+        layer.set_layer(128);
+
+        let overlay = ColorRect::new();
+        overlay.set_anchors_preset(Control::PRESET_WIDE, false);
+        overlay.set_frame_color(Color::rgba(0.0, 0.0, 0.0, 0.0));
+        overlay.set_visible(false);
+        let overlay = overlay.into_shared();
+
+        let layer = layer.into_shared();
+        unsafe {
+            layer.assume_safe().add_child(overlay.clone(), false);
+        }
+        owner.add_child(layer, false);
+
+        self.overlay = Some(overlay);
     }
 
     #[export]
-    pub fn _process(&mut self, mut owner: Node, _delta: f64) {
+    fn _process(&mut self, owner: &Node, delta: f64) {
+        self.elapsed += delta;
+
+        if self.loader.is_some() {
+            self.poll_loader(owner);
+        }
+
+        match self.state {
+            State::Idle => owner.set_process(false),
+            State::Out(time) => {
+                let time = time + delta;
+                if self.update_phase(Phase::Out, time) {
+                    self.end_out_phase(owner);
+                } else {
+                    self.state = State::Out(time);
+                }
+            }
+            State::Loading => {
+                if self.pending_scene.is_some() && self.elapsed >= self.min_display_time {
+                    self.add_scene(owner);
+                }
+            }
+            State::In(time) => {
+                let time = time + delta;
+                if self.update_phase(Phase::In, time) {
+                    self.finish(owner);
+                } else {
+                    self.state = State::In(time);
+                }
+            }
+        }
+    }
+
+    fn end_out_phase(&mut self, owner: &Node) {
+        self.state = State::Loading;
+        self.elapsed = 0.0;
+
+        match self.change {
+            Change::Replace => {
+                if !self.keeps_old_scene() {
+                    self.free_old_scene();
+                    self.current_scene = None;
+                    self.show_loading_screen(owner);
+                }
+            }
+            Change::Push(mode) => {
+                self.suspend_old_scene(mode);
+                if mode == PushMode::Detach {
+                    self.show_loading_screen(owner);
+                }
+            }
+            Change::Pop => {
+                if !self.keeps_old_scene() {
+                    self.free_old_scene();
+                }
+                self.current_scene = None;
+                self.resume_scene(owner);
+            }
+        }
+    }
+
+    /// Reset the overlay and start a transition phase.
+    fn start_phase(&mut self, phase: Phase) {
+        let overlay = match &self.overlay {
+            Some(overlay) => unsafe { overlay.assume_safe() },
+            None => return,
+        };
+
+        overlay.set_visible(self.transition.is_some());
+        overlay.set_material(Null::null());
+        overlay.set_frame_color(Color::rgba(0.0, 0.0, 0.0, 0.0));
+        overlay.set_anchors_and_margins_preset(
+            Control::PRESET_WIDE,
+            Control::PRESET_MODE_MINSIZE,
+            0,
+        );
+
+        let old_scene = self.old_scene.as_ref().map(|n| unsafe { n.assume_safe() });
+        let new_scene = self.new_scene.as_ref().map(|n| unsafe { n.assume_safe() });
+        let ctx = TransitionContext {
+            overlay: overlay.as_ref(),
+            old_scene: old_scene.as_ref().map(|n| n.as_ref()),
+            new_scene: new_scene.as_ref().map(|n| n.as_ref()),
+        };
+
+        if let Some(transition) = self.transition.as_mut() {
+            transition.start(phase, &ctx);
+            transition.update(phase, 0.0, &ctx);
+        }
+    }
+
+    /// Update the current transition phase.
+    /// Returns true once the phase is done.
+    fn update_phase(&mut self, phase: Phase, time: f64) -> bool {
+        let (transition, overlay) = match (self.transition.as_mut(), &self.overlay) {
+            (Some(transition), Some(overlay)) => (transition, unsafe { overlay.assume_safe() }),
+            _ => return true,
+        };
+
+        let duration = transition.duration(phase);
+        let t = if duration > 0.0 {
+            (time / duration).min(1.0)
+        } else {
+            1.0
+        };
+
+        let old_scene = self.old_scene.as_ref().map(|n| unsafe { n.assume_safe() });
+        let new_scene = self.new_scene.as_ref().map(|n| unsafe { n.assume_safe() });
+        let ctx = TransitionContext {
+            overlay: overlay.as_ref(),
+            old_scene: old_scene.as_ref().map(|n| n.as_ref()),
+            new_scene: new_scene.as_ref().map(|n| n.as_ref()),
+        };
+        transition.update(phase, t as f32, &ctx);
+
+        t >= 1.0
+    }
+
+    fn poll_loader(&mut self, owner: &Node) {
         let loader = some_or_bail!(&mut self.loader, "failed to get loader");
-        match loader.inner.poll() {
+        match loader.poll() {
             Ok(()) => {
-                let current = loader.inner.get_stage();
-                let total = loader.inner.get_stage_count();
-                self.update_progress(total, current);
+                let current = loader.get_stage();
+                let total = loader.get_stage_count();
+                self.update_progress(owner, total, current);
             }
-            Err(GodotError::FileEof) => {
-                unsafe { 
-                    owner.set_process(false);
-                    let tree = some_or_bail!(owner.get_tree(), "failed to get scene tree");
-                    let mut root = some_or_bail!(tree.get_root(), "failed to get root node");
-                    let resource = some_or_bail!(loader.inner.get_resource(), "failed to get resource");
-                    let scene = some_or_bail!(resource.cast::<PackedScene>(), "failed to cast resource to packed scene");
-                    root.add_child(scene.instance(0), true);
+            Err(Error::Godot(GodotError::FileEof)) => {
+                let total = loader.get_stage_count();
+                let resource = loader.get_resource();
+                self.loader = None;
+                self.update_progress(owner, total, total);
+
+                let scene = match resource {
+                    Some(resource) => resource.cast::<PackedScene>().ok_or_else(|| Error::Cast {
+                        path: self.path.clone(),
+                        expected: PackedScene::class_name(),
+                    }),
+                    None => Err(Error::Load(self.path.clone())),
+                };
+
+                match scene {
+                    Ok(scene) => self.pending_scene = Some(scene),
+                    Err(e) => self.load_failed(owner, e),
                 }
             }
-            Err(e) => {
-                unsafe { owner.set_process(false) }
-                gd_err!("Error polling loader: {:?}", e);
-                self.loader = None;
+            Err(e) => self.load_failed(owner, e),
+        }
+    }
+
+    /// Stop the scene change after the new scene failed to load.
+    fn load_failed(&mut self, owner: &Node, error: Error) {
+        gd_err!("failed to load scene {}: {}", self.path, error);
+        self.loader = None;
+        self.pending_scene = None;
+        let path = self.path.clone();
+
+        if let (Change::Push(_), State::Loading) = (self.change, self.state) {
+            // Go back to the suspended scene
+            self.hide_loading_screen();
+            self.resume_scene(owner);
+        } else {
+            // During `Phase::Out`, or with `keep_old_scene`, the old scene is still
+            // the current scene. Otherwise it was freed, and there is no current scene.
+            if self.current_scene.is_none() {
+                self.current_path.clear();
             }
+
+            self.old_scene = None;
+            self.finish(owner);
+        }
+
+        owner.emit_signal(
+            "scene_change_failed".into(),
+            &[path.to_variant(), error.to_string().to_variant()],
+        );
+    }
+
+    fn add_scene(&mut self, owner: &Node) {
+        self.hide_loading_screen();
+
+        let scene = some_or_bail!(self.pending_scene.take(), "no scene to add");
+        let tree = some_or_bail!(owner.get_tree(), "failed to get scene tree");
+        let tree = unsafe { tree.assume_safe() };
+        let root = some_or_bail!(tree.get_root(), "failed to get root node");
+        let node = some_or_bail!(
+            unsafe { scene.assume_safe() }.instance(PackedScene::GEN_EDIT_STATE_DISABLED),
+            "failed to instance scene: {}",
+            self.path
+        );
+
+        self.set_current(node.clone(), self.path.clone());
+        unsafe { root.assume_safe() }.add_child(node.clone(), true);
+        tree.set_current_scene(node);
+
+        owner.emit_signal("scene_changed".into(), &[self.path.to_variant()]);
+
+        self.start_phase(Phase::In);
+        self.state = State::In(0.0);
+    }
+
+    /// End the scene change, freeing anything left from the old scene.
+    fn finish(&mut self, owner: &Node) {
+        owner.set_process(false);
+        self.state = State::Idle;
+        self.hide_loading_screen();
+        self.free_old_scene();
+        self.new_scene = None;
+        set_payload(None);
+
+        if let Some(overlay) = &self.overlay {
+            unsafe { overlay.assume_safe() }.set_visible(false);
         }
     }
 
-    fn update_progress(&mut self, total: i64, current: i64) {
-        godot_print!("{} / {}", current, total);
+    fn update_progress(&mut self, owner: &Node, total: i64, current: i64) {
+        owner.emit_signal(
+            "progress".into(),
+            &[current.to_variant(), total.to_variant()],
+        );
     }
 }
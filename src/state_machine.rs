@@ -0,0 +1,645 @@
+//! Finite state machine
+//!
+//! The state machine does not depend on Godot. States change data in a context,
+//! and the script reads and writes the context around `update`:
+//!
+//! ```ignore
+//! #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+//! enum PlayerState {
+//!     Grounded,
+//!     Idle,
+//!     Run,
+//!     Jump,
+//! }
+//!
+//! #[derive(Default)]
+//! struct Body {
+//!     input: Vector2,
+//!     velocity: Vector2,
+//!     on_floor: bool,
+//! }
+//!
+//! fn player_fsm() -> StateMachine<PlayerState, Body> {
+//!     use PlayerState::*;
+//!
+//!     let mut fsm = StateMachine::new(Grounded);
+//!     fsm.state(Grounded).initial(Idle);
+//!     fsm.state(Idle).parent(Grounded);
+//!     fsm.state(Run)
+//!         .parent(Grounded)
+//!         .on_update(|body, _delta| {
+//!             body.velocity.x = body.input.x * SPEED;
+//!             None
+//!         });
+//!     fsm.state(Jump)
+//!         .on_enter(|body| body.velocity.y = -JUMP_SPEED)
+//!         .on_update(|body, delta| {
+//!             body.velocity.y += GRAVITY * delta as f32;
+//!             if body.on_floor { Some(Grounded) } else { None }
+//!         });
+//!
+//!     fsm.add_transition(Idle, Run, |body| body.input.x != 0.0);
+//!     fsm.add_transition(Run, Idle, |body| body.input.x == 0.0);
+//!     // Applies to both Idle and Run
+//!     fsm.add_transition(Grounded, Jump, |body| body.input.y < 0.0);
+//!     fsm
+//! }
+//!
+//! // In the script
+//! fn _physics_process(&mut self, owner: &KinematicBody2D, delta: f64) {
+//!     self.body.on_floor = owner.is_on_floor();
+//!     self.fsm.update(&mut self.body, delta);
+//!     self.body.velocity = owner.move_and_slide_default(self.body.velocity, UP_2D);
+//! }
+//! ```
+//!
+//! ## Hierarchy
+//!
+//! A state can have a parent. While a state is active all of its ancestors are
+//! active too: their update hooks run (outermost first), and their transitions apply.
+//! Changing to a state with an initial child enters the initial child.
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+type EnterFn<Ctx> = Box<dyn FnMut(&mut Ctx) + Send>;
+type UpdateFn<S, Ctx> = Box<dyn FnMut(&mut Ctx, f64) -> Option<S> + Send>;
+type GuardFn<Ctx> = Box<dyn Fn(&Ctx) -> bool + Send>;
+type TransitionHook<S> = Box<dyn FnMut(S, S) + Send>;
+
+/// Number of previous states kept by default
+pub const DEFAULT_HISTORY_SIZE: usize = 16;
+
+// -----------------------------------------------------------------------------
+//     - State -
+// -----------------------------------------------------------------------------
+struct StateDef<S, Ctx> {
+    parent: Option<S>,
+    initial: Option<S>,
+    enter: Option<EnterFn<Ctx>>,
+    exit: Option<EnterFn<Ctx>>,
+    update: Option<UpdateFn<S, Ctx>>,
+}
+
+impl<S, Ctx> Default for StateDef<S, Ctx> {
+    fn default() -> Self {
+        Self {
+            parent: None,
+            initial: None,
+            enter: None,
+            exit: None,
+            update: None,
+        }
+    }
+}
+
+/// Configure a state, see `StateMachine::state`.
+pub struct StateBuilder<'a, S, Ctx> {
+    def: &'a mut StateDef<S, Ctx>,
+}
+
+impl<'a, S, Ctx> StateBuilder<'a, S, Ctx> {
+    /// Make the state a sub-state of `parent`.
+    pub fn parent(self, parent: S) -> Self {
+        self.def.parent = Some(parent);
+        self
+    }
+
+    /// Sub-state entered when changing to this state.
+    pub fn initial(self, child: S) -> Self {
+        self.def.initial = Some(child);
+        self
+    }
+
+    pub fn on_enter<F>(self, f: F) -> Self
+    where
+        F: FnMut(&mut Ctx) + Send + 'static,
+    {
+        self.def.enter = Some(Box::new(f));
+        self
+    }
+
+    pub fn on_exit<F>(self, f: F) -> Self
+    where
+        F: FnMut(&mut Ctx) + Send + 'static,
+    {
+        self.def.exit = Some(Box::new(f));
+        self
+    }
+
+    /// Called every update with the delta time.
+    /// Return a state to change to it.
+    pub fn on_update<F>(self, f: F) -> Self
+    where
+        F: FnMut(&mut Ctx, f64) -> Option<S> + Send + 'static,
+    {
+        self.def.update = Some(Box::new(f));
+        self
+    }
+}
+
+struct TransitionDef<S, Ctx> {
+    /// `None` for any state
+    from: Option<S>,
+    to: S,
+    guard: GuardFn<Ctx>,
+}
+
+// -----------------------------------------------------------------------------
+//     - State machine -
+// -----------------------------------------------------------------------------
+pub struct StateMachine<S, Ctx> {
+    states: HashMap<S, StateDef<S, Ctx>>,
+    transitions: Vec<TransitionDef<S, Ctx>>,
+    initial: S,
+    current: S,
+    started: bool,
+    time_in_state: f64,
+    history: VecDeque<S>,
+    history_size: usize,
+    on_transition: Option<TransitionHook<S>>,
+}
+
+impl<S, Ctx> StateMachine<S, Ctx>
+where
+    S: Copy + Eq + Hash,
+{
+    /// Create a state machine starting in `initial`.
+    /// The initial state is entered on `start` or the first `update`.
+    pub fn new(initial: S) -> Self {
+        Self {
+            states: HashMap::new(),
+            transitions: Vec::new(),
+            initial,
+            current: initial,
+            started: false,
+            time_in_state: 0.0,
+            history: VecDeque::new(),
+            history_size: DEFAULT_HISTORY_SIZE,
+            on_transition: None,
+        }
+    }
+
+    /// Configure a state.
+    /// States without any configuration don't have to be added.
+    pub fn state(&mut self, state: S) -> StateBuilder<'_, S, Ctx> {
+        StateBuilder {
+            def: self.states.entry(state).or_default(),
+        }
+    }
+
+    /// Change from `from` (or any of its sub-states) to `to` when `guard` returns true.
+    /// Transitions are checked in the order they were added.
+    pub fn add_transition<F>(&mut self, from: S, to: S, guard: F)
+    where
+        F: Fn(&Ctx) -> bool + Send + 'static,
+    {
+        self.transitions.push(TransitionDef {
+            from: Some(from),
+            to,
+            guard: Box::new(guard),
+        });
+    }
+
+    /// Change from any state to `to` when `guard` returns true.
+    pub fn add_any_transition<F>(&mut self, to: S, guard: F)
+    where
+        F: Fn(&Ctx) -> bool + Send + 'static,
+    {
+        self.transitions.push(TransitionDef {
+            from: None,
+            to,
+            guard: Box::new(guard),
+        });
+    }
+
+    /// Called with `(from, to)` on every state change, e.g. for debug output.
+    pub fn on_transition<F>(&mut self, f: F)
+    where
+        F: FnMut(S, S) + Send + 'static,
+    {
+        self.on_transition = Some(Box::new(f));
+    }
+
+    /// Number of previous states to keep.
+    pub fn set_history_size(&mut self, size: usize) {
+        self.history_size = size;
+        self.history.truncate(size);
+    }
+
+    /// The active state, without its ancestors.
+    pub fn current(&self) -> S {
+        self.current
+    }
+
+    /// True if `state` is the current state or one of its ancestors.
+    pub fn is_in(&self, state: S) -> bool {
+        self.path(self.current).contains(&state)
+    }
+
+    /// Seconds since the current state was entered.
+    pub fn time_in_state(&self) -> f64 {
+        self.time_in_state
+    }
+
+    /// Previous states, most recent first.
+    pub fn history(&self) -> impl Iterator<Item = S> + '_ {
+        self.history.iter().copied()
+    }
+
+    pub fn previous(&self) -> Option<S> {
+        self.history.front().copied()
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.started
+    }
+
+    /// Enter the initial state.
+    /// Does nothing if the state machine is already started.
+    pub fn start(&mut self, ctx: &mut Ctx) {
+        if self.started {
+            return;
+        }
+
+        self.started = true;
+        self.current = self.resolve(self.initial);
+        for state in self.path(self.current) {
+            self.enter(state, ctx);
+        }
+    }
+
+    /// Check transitions, then run the update hooks of the active states.
+    pub fn update(&mut self, ctx: &mut Ctx, delta: f64) {
+        self.start(ctx);
+
+        if let Some(to) = self.check_transitions(ctx) {
+            self.change(ctx, to);
+        }
+
+        self.time_in_state += delta;
+
+        let mut next = None;
+        for state in self.path(self.current) {
+            let update = self
+                .states
+                .get_mut(&state)
+                .and_then(|def| def.update.as_mut());
+            if let Some(update) = update {
+                if let Some(to) = update(ctx, delta) {
+                    next.get_or_insert(to);
+                }
+            }
+        }
+
+        if let Some(to) = next {
+            self.change(ctx, to);
+        }
+    }
+
+    /// Change state, ignoring guards.
+    /// Changing to the current state exits and re-enters it.
+    pub fn change(&mut self, ctx: &mut Ctx, to: S) {
+        self.change_to(ctx, to, true);
+    }
+
+    /// Change back to the previous state.
+    /// Returns false if there is no previous state.
+    pub fn back(&mut self, ctx: &mut Ctx) -> bool {
+        match self.history.pop_front() {
+            Some(previous) => {
+                self.change_to(ctx, previous, false);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn change_to(&mut self, ctx: &mut Ctx, to: S, record: bool) {
+        self.start(ctx);
+
+        let from = self.current;
+        let to = self.resolve(to);
+        let from_path = self.path(from);
+        let to_path = self.path(to);
+
+        let mut common = from_path
+            .iter()
+            .zip(to_path.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        // Re-enter the target when changing to the current state or an ancestor of it
+        if common == to_path.len() {
+            common -= 1;
+        }
+
+        for state in from_path[common..].iter().rev() {
+            self.exit(*state, ctx);
+        }
+
+        self.current = to;
+        self.time_in_state = 0.0;
+
+        if record && self.history_size > 0 {
+            self.history.push_front(from);
+            self.history.truncate(self.history_size);
+        }
+
+        for state in &to_path[common..] {
+            self.enter(*state, ctx);
+        }
+
+        if let Some(hook) = self.on_transition.as_mut() {
+            hook(from, to);
+        }
+    }
+
+    fn check_transitions(&self, ctx: &Ctx) -> Option<S> {
+        let path = self.path(self.current);
+
+        self.transitions
+            .iter()
+            .filter(|t| t.from.map(|from| path.contains(&from)).unwrap_or(true))
+            .filter(|t| self.resolve(t.to) != self.current)
+            .find(|t| (t.guard)(ctx))
+            .map(|t| t.to)
+    }
+
+    fn enter(&mut self, state: S, ctx: &mut Ctx) {
+        if let Some(enter) = self
+            .states
+            .get_mut(&state)
+            .and_then(|def| def.enter.as_mut())
+        {
+            enter(ctx);
+        }
+    }
+
+    fn exit(&mut self, state: S, ctx: &mut Ctx) {
+        if let Some(exit) = self
+            .states
+            .get_mut(&state)
+            .and_then(|def| def.exit.as_mut())
+        {
+            exit(ctx);
+        }
+    }
+
+    // Follow initial sub-states down to a leaf
+    fn resolve(&self, mut state: S) -> S {
+        for _ in 0..=self.states.len() {
+            match self.states.get(&state).and_then(|def| def.initial) {
+                Some(child) => state = child,
+                None => break,
+            }
+        }
+        state
+    }
+
+    // The state and its ancestors, outermost first
+    fn path(&self, state: S) -> Vec<S> {
+        let mut path = vec![state];
+        let mut current = state;
+
+        for _ in 0..self.states.len() {
+            match self.states.get(&current).and_then(|def| def.parent) {
+                Some(parent) if !path.contains(&parent) => {
+                    path.push(parent);
+                    current = parent;
+                }
+                _ => break,
+            }
+        }
+
+        path.reverse();
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    enum State {
+        Grounded,
+        Idle,
+        Run,
+        Air,
+        Jump,
+        Fall,
+    }
+
+    use State::*;
+
+    #[derive(Default)]
+    struct Ctx {
+        run: bool,
+        jump: bool,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Ctx {
+        fn take_log(&mut self) -> Vec<String> {
+            std::mem::take(&mut *self.log.lock().unwrap())
+        }
+    }
+
+    fn fsm(ctx: &Ctx) -> StateMachine<State, Ctx> {
+        let mut fsm = StateMachine::new(Grounded);
+        for &state in &[Grounded, Idle, Run, Air, Jump, Fall] {
+            fsm.state(state)
+                .on_enter(move |ctx: &mut Ctx| {
+                    ctx.log.lock().unwrap().push(format!("enter {:?}", state))
+                })
+                .on_exit(move |ctx: &mut Ctx| {
+                    ctx.log.lock().unwrap().push(format!("exit {:?}", state))
+                });
+        }
+        fsm.state(Grounded).initial(Idle);
+        fsm.state(Idle).parent(Grounded);
+        fsm.state(Run).parent(Grounded);
+        fsm.state(Air).initial(Jump);
+        fsm.state(Jump).parent(Air);
+        fsm.state(Fall).parent(Air);
+
+        let log = ctx.log.clone();
+        fsm.on_transition(move |from, to| {
+            log.lock()
+                .unwrap()
+                .push(format!("transition {:?} {:?}", from, to))
+        });
+        fsm
+    }
+
+    #[test]
+    fn start_enters_initial_path() {
+        let mut ctx = Ctx::default();
+        let mut fsm = fsm(&ctx);
+        fsm.start(&mut ctx);
+
+        assert_eq!(fsm.current(), Idle);
+        assert!(fsm.is_in(Grounded));
+        assert!(!fsm.is_in(Air));
+        assert_eq!(ctx.take_log(), ["enter Grounded", "enter Idle"]);
+    }
+
+    #[test]
+    fn change_between_siblings_keeps_parent() {
+        let mut ctx = Ctx::default();
+        let mut fsm = fsm(&ctx);
+        fsm.start(&mut ctx);
+        ctx.take_log();
+
+        fsm.change(&mut ctx, Run);
+        assert_eq!(
+            ctx.take_log(),
+            ["exit Idle", "enter Run", "transition Idle Run"]
+        );
+    }
+
+    #[test]
+    fn change_across_hierarchy_exits_to_common_ancestor() {
+        let mut ctx = Ctx::default();
+        let mut fsm = fsm(&ctx);
+        fsm.change(&mut ctx, Run);
+        ctx.take_log();
+
+        fsm.change(&mut ctx, Fall);
+        assert_eq!(
+            ctx.take_log(),
+            [
+                "exit Run",
+                "exit Grounded",
+                "enter Air",
+                "enter Fall",
+                "transition Run Fall"
+            ]
+        );
+    }
+
+    #[test]
+    fn change_to_parent_enters_initial_child() {
+        let mut ctx = Ctx::default();
+        let mut fsm = fsm(&ctx);
+        fsm.start(&mut ctx);
+        ctx.take_log();
+
+        fsm.change(&mut ctx, Air);
+        assert_eq!(fsm.current(), Jump);
+        assert_eq!(
+            ctx.take_log(),
+            [
+                "exit Idle",
+                "exit Grounded",
+                "enter Air",
+                "enter Jump",
+                "transition Idle Jump"
+            ]
+        );
+    }
+
+    #[test]
+    fn change_to_current_state_reenters_it() {
+        let mut ctx = Ctx::default();
+        let mut fsm = fsm(&ctx);
+        fsm.start(&mut ctx);
+        ctx.take_log();
+
+        fsm.change(&mut ctx, Idle);
+        assert_eq!(
+            ctx.take_log(),
+            ["exit Idle", "enter Idle", "transition Idle Idle"]
+        );
+    }
+
+    #[test]
+    fn guard_rejects_transition() {
+        let mut ctx = Ctx::default();
+        let mut fsm = fsm(&ctx);
+        fsm.add_transition(Idle, Run, |ctx| ctx.run);
+
+        fsm.update(&mut ctx, 0.1);
+        assert_eq!(fsm.current(), Idle);
+        assert!((fsm.time_in_state() - 0.1).abs() < 1e-9);
+
+        ctx.run = true;
+        fsm.update(&mut ctx, 0.1);
+        assert_eq!(fsm.current(), Run);
+    }
+
+    #[test]
+    fn parent_transition_applies_to_children() {
+        let mut ctx = Ctx::default();
+        let mut fsm = fsm(&ctx);
+        fsm.add_transition(Idle, Run, |ctx| ctx.run);
+        fsm.add_transition(Grounded, Jump, |ctx| ctx.jump);
+
+        ctx.run = true;
+        fsm.update(&mut ctx, 0.1);
+        assert_eq!(fsm.current(), Run);
+
+        ctx.jump = true;
+        fsm.update(&mut ctx, 0.1);
+        assert_eq!(fsm.current(), Jump);
+    }
+
+    #[test]
+    fn update_hook_changes_state() {
+        let mut ctx = Ctx::default();
+        let mut fsm = fsm(&ctx);
+        fsm.state(Jump).on_update(|_, _| Some(Fall));
+        fsm.change(&mut ctx, Jump);
+
+        fsm.update(&mut ctx, 0.1);
+        assert_eq!(fsm.current(), Fall);
+        assert_eq!(fsm.time_in_state(), 0.0);
+    }
+
+    #[test]
+    fn back_returns_to_previous_states() {
+        let mut ctx = Ctx::default();
+        let mut fsm = fsm(&ctx);
+        fsm.change(&mut ctx, Run);
+        fsm.change(&mut ctx, Fall);
+
+        assert_eq!(fsm.history().collect::<Vec<_>>(), [Run, Idle]);
+        assert_eq!(fsm.previous(), Some(Run));
+        ctx.take_log();
+
+        assert!(fsm.back(&mut ctx));
+        assert_eq!(fsm.current(), Run);
+        assert_eq!(
+            ctx.take_log(),
+            [
+                "exit Fall",
+                "exit Air",
+                "enter Grounded",
+                "enter Run",
+                "transition Fall Run"
+            ]
+        );
+
+        assert!(fsm.back(&mut ctx));
+        assert_eq!(fsm.current(), Idle);
+        assert!(!fsm.back(&mut ctx));
+        assert_eq!(fsm.current(), Idle);
+    }
+
+    #[test]
+    fn history_is_truncated() {
+        let mut ctx = Ctx::default();
+        let mut fsm = fsm(&ctx);
+        fsm.change(&mut ctx, Run);
+        fsm.change(&mut ctx, Jump);
+        fsm.change(&mut ctx, Fall);
+        assert_eq!(fsm.history().collect::<Vec<_>>(), [Jump, Run, Idle]);
+
+        fsm.set_history_size(1);
+        assert_eq!(fsm.history().collect::<Vec<_>>(), [Jump]);
+
+        fsm.change(&mut ctx, Idle);
+        assert_eq!(fsm.history().collect::<Vec<_>>(), [Fall]);
+    }
+}
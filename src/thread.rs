@@ -0,0 +1,254 @@
+//! Values tied to a thread.
+//!
+//! Godot objects are not thread safe, but scripts using the default user data
+//! have to be `Send`. A `ThreadBound` value can be moved between threads,
+//! but panics when used from any thread other than the one that created it,
+//! so misuse from e.g. a rayon worker is caught instead of being undefined behaviour.
+//!
+//! To bind a value created on a worker to the main thread with `ThreadBound::for_main`,
+//! the main thread has to be recorded once:
+//!
+//! ```ignore
+//! // lib.rs
+//! fn init(handle: init::InitHandle) {
+//!     gdextras::thread::init_main();
+//! }
+//! ```
+//!
+//! ```ignore
+//! #[derive(NativeClass)]
+//! #[inherit(Node2D)]
+//! pub struct Player {
+//!     target: Option<ThreadBound<Ref<Node2D>>>,
+//! }
+//!
+//! // On the main thread
+//! if let Some(target) = &self.target {
+//!     let target = unsafe { target.assume_safe() };
+//!     owner.look_at(target.get_global_position());
+//! }
+//! ```
+use std::any::type_name;
+use std::fmt;
+use std::mem::{self, ManuallyDrop};
+use std::ops::{Deref, DerefMut};
+use std::sync::OnceLock;
+use std::thread::{self, ThreadId};
+
+use crate::error::{Error, Result};
+use crate::{gd_err, gd_panic};
+
+static MAIN_THREAD: OnceLock<ThreadId> = OnceLock::new();
+
+/// Record the current thread as the main thread.
+/// Call this from the gdnative init function. Later calls are ignored.
+pub fn init_main() {
+    let _ = MAIN_THREAD.set(thread::current().id());
+}
+
+/// The main thread, or `None` if `init_main` has not been called.
+pub fn main_thread() -> Option<ThreadId> {
+    MAIN_THREAD.get().copied()
+}
+
+/// True if called from the main thread.
+pub fn is_main_thread() -> bool {
+    main_thread() == Some(thread::current().id())
+}
+
+pub struct ThreadBound<T> {
+    value: ManuallyDrop<T>,
+    thread: ThreadId,
+}
+
+// The value is only ever accessed or dropped on the thread it was created on.
+unsafe impl<T> Send for ThreadBound<T> {}
+unsafe impl<T> Sync for ThreadBound<T> {}
+
+impl<T: Send> ThreadBound<T> {
+    /// Bind the value to the main thread, e.g. when created on a worker.
+    /// Returns an error if `init_main` has not been called.
+    pub fn for_main(value: T) -> Result<Self> {
+        let thread = main_thread().ok_or(Error::NoMainThread)?;
+
+        Ok(Self {
+            value: ManuallyDrop::new(value),
+            thread,
+        })
+    }
+}
+
+impl<T> ThreadBound<T> {
+    /// Bind the value to the current thread.
+    pub fn new(value: T) -> Self {
+        Self {
+            value: ManuallyDrop::new(value),
+            thread: thread::current().id(),
+        }
+    }
+
+    /// The thread the value is bound to.
+    pub fn thread(&self) -> ThreadId {
+        self.thread
+    }
+
+    /// True if the value can be used from the current thread.
+    pub fn is_valid(&self) -> bool {
+        thread::current().id() == self.thread
+    }
+
+    /// Get the value, or `None` if called from another thread.
+    pub fn try_get(&self) -> Option<&T> {
+        if self.is_valid() {
+            Some(&self.value)
+        } else {
+            None
+        }
+    }
+
+    /// Get the value mutably, or `None` if called from another thread.
+    pub fn try_get_mut(&mut self) -> Option<&mut T> {
+        if self.is_valid() {
+            Some(&mut self.value)
+        } else {
+            None
+        }
+    }
+
+    /// Get the value.
+    /// Panics if called from another thread.
+    pub fn get(&self) -> &T {
+        self.assert_thread();
+        &self.value
+    }
+
+    /// Get the value mutably.
+    /// Panics if called from another thread.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.assert_thread();
+        &mut self.value
+    }
+
+    /// Take the value out.
+    /// Panics if called from another thread.
+    pub fn into_inner(mut self) -> T {
+        self.assert_thread();
+        let value = unsafe { ManuallyDrop::take(&mut self.value) };
+        mem::forget(self);
+        value
+    }
+
+    fn assert_thread(&self) {
+        if !self.is_valid() {
+            gd_panic!(
+                "ThreadBound<{}> used from thread {:?}, but is bound to thread {:?}",
+                type_name::<T>(),
+                thread::current().id(),
+                self.thread
+            );
+        }
+    }
+}
+
+impl<T> Deref for ThreadBound<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.get()
+    }
+}
+
+impl<T> DerefMut for ThreadBound<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.get_mut()
+    }
+}
+
+impl<T> Drop for ThreadBound<T> {
+    fn drop(&mut self) {
+        if self.is_valid() {
+            unsafe { ManuallyDrop::drop(&mut self.value) };
+        } else {
+            // Dropping the value here is as unsafe as using it, so leak it instead
+            gd_err!(
+                "ThreadBound<{}> dropped on thread {:?}, but is bound to thread {:?}. The value is leaked.",
+                type_name::<T>(),
+                thread::current().id(),
+                self.thread
+            );
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for ThreadBound<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_get() {
+            Some(value) => f.debug_tuple("ThreadBound").field(value).finish(),
+            None => f.debug_tuple("ThreadBound").field(&self.thread).finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    struct DropFlag(Arc<AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn bound_to_the_creating_thread() {
+        let bound = thread::spawn(|| {
+            let bound = ThreadBound::new(5);
+            assert!(bound.is_valid());
+            assert_eq!(bound.thread(), thread::current().id());
+            bound.into_inner()
+        })
+        .join()
+        .unwrap();
+        assert_eq!(bound, 5);
+    }
+
+    #[test]
+    fn try_get_from_another_thread_is_none() {
+        let mut bound = ThreadBound::new(5);
+
+        thread::spawn(move || {
+            assert!(!bound.is_valid());
+            assert!(bound.try_get().is_none());
+            assert!(bound.try_get_mut().is_none());
+            std::mem::forget(bound);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn for_main_is_bound_to_main() {
+        init_main();
+        let main = main_thread();
+        let bound = thread::spawn(|| ThreadBound::for_main(5))
+            .join()
+            .unwrap()
+            .expect("main thread not recorded");
+        assert_eq!(Some(bound.thread()), main);
+        std::mem::forget(bound);
+    }
+
+    #[test]
+    fn drop_on_another_thread_leaks() {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let bound = ThreadBound::new(DropFlag(dropped.clone()));
+
+        let result = thread::spawn(move || drop(bound)).join();
+        assert!(result.is_ok());
+        assert!(!dropped.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,236 @@
+//! Scene transitions
+//! Effects run by the `SceneLoader` before and after swapping scenes.
+//!
+//! A transition runs in two phases:
+//! * `Phase::Out` hides the current scene, before the new scene is added.
+//! * `Phase::In` reveals the new scene once it is in the tree.
+//!
+//! Effects draw on a full screen `ColorRect` overlay, placed on a canvas layer
+//! above the scenes, or modulate the scenes directly.
+//!
+//! ```ignore
+//! SceneLoader::with_autoload(owner, |loader, node| {
+//!     loader.set_transition(Some(Box::new(Fade::new(Color::rgb(0.0, 0.0, 0.0), 0.5))));
+//!     loader.change_scene(&node, "res://World.tscn")
+//! })??;
+//! ```
+use gdnative::api::{CanvasItem, ColorRect, Control, Node, ShaderMaterial};
+use gdnative::{Color, Ref, Variant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Out,
+    In,
+}
+
+/// Nodes available to a transition.
+pub struct TransitionContext<'a> {
+    /// Full screen overlay, drawn above all scenes
+    pub overlay: &'a ColorRect,
+    /// The scene being replaced, if it is still alive
+    pub old_scene: Option<&'a Node>,
+    /// The incoming scene, only available during `Phase::In`
+    pub new_scene: Option<&'a Node>,
+}
+
+pub trait Transition: Send {
+    /// Duration of a phase in seconds.
+    fn duration(&self, phase: Phase) -> f64;
+
+    /// Called once at the start of each phase.
+    fn start(&mut self, _phase: Phase, _ctx: &TransitionContext) {}
+
+    /// Update the effect, where `t` goes from 0.0 to 1.0 over the phase.
+    fn update(&mut self, phase: Phase, t: f32, ctx: &TransitionContext);
+
+    /// Keep the old scene alive until the new scene is ready,
+    /// instead of freeing it at the end of `Phase::Out`.
+    fn keep_old_scene(&self) -> bool {
+        false
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Fade -
+// -----------------------------------------------------------------------------
+/// Fade to a colour, then fade back in.
+pub struct Fade {
+    color: Color,
+    duration: f64,
+}
+
+impl Fade {
+    pub fn new(color: Color, duration: f64) -> Self {
+        Self { color, duration }
+    }
+}
+
+impl Transition for Fade {
+    fn duration(&self, _phase: Phase) -> f64 {
+        self.duration
+    }
+
+    fn update(&mut self, phase: Phase, t: f32, ctx: &TransitionContext) {
+        let alpha = match phase {
+            Phase::Out => t,
+            Phase::In => 1.0 - t,
+        };
+
+        let mut color = self.color;
+        color.a = alpha;
+        ctx.overlay.set_frame_color(color);
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Crossfade -
+// -----------------------------------------------------------------------------
+/// Fade the new scene in over the old scene.
+/// Only scenes with a `CanvasItem` root are faded, and canvas layers
+/// inside the scenes are not affected.
+pub struct Crossfade {
+    duration: f64,
+}
+
+impl Crossfade {
+    pub fn new(duration: f64) -> Self {
+        Self { duration }
+    }
+}
+
+impl Transition for Crossfade {
+    fn duration(&self, phase: Phase) -> f64 {
+        match phase {
+            Phase::Out => 0.0,
+            Phase::In => self.duration,
+        }
+    }
+
+    fn update(&mut self, phase: Phase, t: f32, ctx: &TransitionContext) {
+        if phase == Phase::Out {
+            return;
+        }
+
+        if let Some(item) = ctx.new_scene.and_then(|n| n.cast::<CanvasItem>()) {
+            let mut modulate = item.get_modulate();
+            modulate.a = t;
+            item.set_modulate(modulate);
+        }
+    }
+
+    fn keep_old_scene(&self) -> bool {
+        true
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Wipe -
+// -----------------------------------------------------------------------------
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WipeDirection {
+    LeftToRight,
+    RightToLeft,
+    TopToBottom,
+    BottomToTop,
+}
+
+/// Cover the screen with a colour from one edge, then uncover it
+/// continuing in the same direction.
+pub struct Wipe {
+    color: Color,
+    direction: WipeDirection,
+    duration: f64,
+}
+
+impl Wipe {
+    pub fn new(color: Color, direction: WipeDirection, duration: f64) -> Self {
+        Self {
+            color,
+            direction,
+            duration,
+        }
+    }
+}
+
+impl Transition for Wipe {
+    fn duration(&self, _phase: Phase) -> f64 {
+        self.duration
+    }
+
+    fn start(&mut self, _phase: Phase, ctx: &TransitionContext) {
+        ctx.overlay.set_frame_color(self.color);
+    }
+
+    fn update(&mut self, phase: Phase, t: f32, ctx: &TransitionContext) {
+        let t = t as f64;
+
+        // Covered range along the wipe axis
+        let (start, end) = match phase {
+            Phase::Out => (0.0, t),
+            Phase::In => (t, 1.0),
+        };
+
+        let (first_margin, second_margin, first, second) = match self.direction {
+            WipeDirection::LeftToRight => (Control::MARGIN_LEFT, Control::MARGIN_RIGHT, start, end),
+            WipeDirection::RightToLeft => (
+                Control::MARGIN_LEFT,
+                Control::MARGIN_RIGHT,
+                1.0 - end,
+                1.0 - start,
+            ),
+            WipeDirection::TopToBottom => (Control::MARGIN_TOP, Control::MARGIN_BOTTOM, start, end),
+            WipeDirection::BottomToTop => (
+                Control::MARGIN_TOP,
+                Control::MARGIN_BOTTOM,
+                1.0 - end,
+                1.0 - start,
+            ),
+        };
+
+        ctx.overlay.set_anchor(first_margin, first, false, false);
+        ctx.overlay.set_anchor(second_margin, second, false, false);
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Shader -
+// -----------------------------------------------------------------------------
+/// Drive a shader on the overlay.
+/// The shader parameter goes from 0.0 to 1.0 while covering the screen,
+/// and back to 0.0 while revealing the new scene.
+pub struct ShaderTransition {
+    material: Ref<ShaderMaterial>,
+    param: String,
+    duration: f64,
+}
+
+impl ShaderTransition {
+    pub fn new(material: Ref<ShaderMaterial>, param: &str, duration: f64) -> Self {
+        Self {
+            material,
+            param: param.to_string(),
+            duration,
+        }
+    }
+}
+
+impl Transition for ShaderTransition {
+    fn duration(&self, _phase: Phase) -> f64 {
+        self.duration
+    }
+
+    fn start(&mut self, _phase: Phase, ctx: &TransitionContext) {
+        ctx.overlay.set_material(self.material.clone());
+        ctx.overlay.set_frame_color(Color::rgba(1.0, 1.0, 1.0, 1.0));
+    }
+
+    fn update(&mut self, phase: Phase, t: f32, _ctx: &TransitionContext) {
+        let value = match phase {
+            Phase::Out => t,
+            Phase::In => 1.0 - t,
+        };
+
+        unsafe { self.material.assume_safe() }
+            .set_shader_param(self.param.as_str().into(), Variant::from_f64(value as f64));
+    }
+}
@@ -0,0 +1,980 @@
+//! Tweens without `Tween` nodes.
+//!
+//! Tweens do not depend on Godot, and are advanced with the delta time
+//! from `_process`:
+//!
+//! ```ignore
+//! let alpha = TweenTarget::new(0.0);
+//! let position = owner.position();
+//! let node = unsafe { owner.assume_shared() };
+//!
+//! let intro = Sequence::new()
+//!     .then(Tween::new(0.0, 1.0, 0.5).with_target(&alpha))
+//!     .delay(0.2)
+//!     .then(
+//!         Tween::new(position, position + Vector2::new(0.0, -40.0), 1.0)
+//!             .with_ease(Ease::Out(Curve::Back))
+//!             .on_update(move |p| unsafe { node.assume_safe() }.set_position(p)),
+//!     )
+//!     .call(|| gdp!("intro done"));
+//!
+//! self.tweens.add(intro);
+//!
+//! // In the script
+//! fn _process(&mut self, owner: &Node2D, delta: f64) {
+//!     self.tweens.update(delta);
+//!     let mut modulate = owner.modulate();
+//!     modulate.a = self.alpha.get();
+//!     owner.set_modulate(modulate);
+//! }
+//! ```
+use gdnative::{Color, Vector2, Vector3};
+use std::f32::consts::PI;
+use std::sync::{Arc, Mutex};
+
+type UpdateFn<T> = Box<dyn FnMut(T) + Send>;
+type CallFn = Box<dyn FnMut() + Send>;
+
+// -----------------------------------------------------------------------------
+//     - Easing -
+// -----------------------------------------------------------------------------
+/// Robert Penner's easing curves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Curve {
+    Sine,
+    Quad,
+    Cubic,
+    Quart,
+    Quint,
+    Expo,
+    Circ,
+    Back,
+    Elastic,
+    Bounce,
+}
+
+impl Curve {
+    fn ease_in(self, t: f32) -> f32 {
+        match self {
+            Curve::Sine => 1.0 - (t * PI / 2.0).cos(),
+            Curve::Quad => t * t,
+            Curve::Cubic => t.powi(3),
+            Curve::Quart => t.powi(4),
+            Curve::Quint => t.powi(5),
+            Curve::Expo if t <= 0.0 => 0.0,
+            Curve::Expo => 2f32.powf(10.0 * t - 10.0),
+            Curve::Circ => 1.0 - (1.0 - t * t).max(0.0).sqrt(),
+            Curve::Back => BACK_C3 * t.powi(3) - BACK_C1 * t * t,
+            Curve::Elastic if t <= 0.0 => 0.0,
+            Curve::Elastic if t >= 1.0 => 1.0,
+            Curve::Elastic => {
+                -(2f32.powf(10.0 * t - 10.0)) * ((t * 10.0 - 10.75) * ELASTIC_C4).sin()
+            }
+            Curve::Bounce => 1.0 - bounce_out(1.0 - t),
+        }
+    }
+
+    fn ease_out(self, t: f32) -> f32 {
+        match self {
+            Curve::Bounce => bounce_out(t),
+            curve => 1.0 - curve.ease_in(1.0 - t),
+        }
+    }
+
+    fn ease_in_out(self, t: f32) -> f32 {
+        match self {
+            // Back and elastic use a different shape when combined
+            Curve::Back if t < 0.5 => {
+                (2.0 * t).powi(2) * ((BACK_C2 + 1.0) * 2.0 * t - BACK_C2) / 2.0
+            }
+            Curve::Back => {
+                ((2.0 * t - 2.0).powi(2) * ((BACK_C2 + 1.0) * (t * 2.0 - 2.0) + BACK_C2) + 2.0)
+                    / 2.0
+            }
+            Curve::Elastic if t <= 0.0 => 0.0,
+            Curve::Elastic if t >= 1.0 => 1.0,
+            Curve::Elastic if t < 0.5 => {
+                -(2f32.powf(20.0 * t - 10.0) * ((20.0 * t - 11.125) * ELASTIC_C5).sin()) / 2.0
+            }
+            Curve::Elastic => {
+                2f32.powf(-20.0 * t + 10.0) * ((20.0 * t - 11.125) * ELASTIC_C5).sin() / 2.0 + 1.0
+            }
+            curve if t < 0.5 => curve.ease_in(2.0 * t) / 2.0,
+            curve => 1.0 - curve.ease_in(2.0 - 2.0 * t) / 2.0,
+        }
+    }
+}
+
+const BACK_C1: f32 = 1.70158;
+const BACK_C2: f32 = BACK_C1 * 1.525;
+const BACK_C3: f32 = BACK_C1 + 1.0;
+const ELASTIC_C4: f32 = 2.0 * PI / 3.0;
+const ELASTIC_C5: f32 = 2.0 * PI / 4.5;
+
+fn bounce_out(t: f32) -> f32 {
+    const N1: f32 = 7.5625;
+    const D1: f32 = 2.75;
+
+    if t < 1.0 / D1 {
+        N1 * t * t
+    } else if t < 2.0 / D1 {
+        let t = t - 1.5 / D1;
+        N1 * t * t + 0.75
+    } else if t < 2.5 / D1 {
+        let t = t - 2.25 / D1;
+        N1 * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D1;
+        N1 * t * t + 0.984375
+    }
+}
+
+/// Easing function, e.g. `Ease::Out(Curve::Quad)`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Ease {
+    #[default]
+    Linear,
+    In(Curve),
+    Out(Curve),
+    InOut(Curve),
+}
+
+impl Ease {
+    /// Ease `t` between 0 and 1.
+    /// Back and elastic curves go outside of 0 and 1.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Ease::Linear => t,
+            Ease::In(curve) => curve.ease_in(t),
+            Ease::Out(curve) => curve.ease_out(t),
+            Ease::InOut(curve) => curve.ease_in_out(t),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Values -
+// -----------------------------------------------------------------------------
+/// A value that can be tweened.
+pub trait Lerp: Copy + Send + 'static {
+    /// Interpolate between `self` and `to`.
+    /// `t` can be outside of 0 and 1 for overshooting curves.
+    fn lerp(self, to: Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(self, to: Self, t: f32) -> Self {
+        self + (to - self) * t
+    }
+}
+
+impl Lerp for Vector2 {
+    fn lerp(self, to: Self, t: f32) -> Self {
+        self + (to - self) * t
+    }
+}
+
+impl Lerp for Vector3 {
+    fn lerp(self, to: Self, t: f32) -> Self {
+        self + (to - self) * t
+    }
+}
+
+impl Lerp for Color {
+    fn lerp(self, to: Self, t: f32) -> Self {
+        Color::rgba(
+            Lerp::lerp(self.r, to.r, t),
+            Lerp::lerp(self.g, to.g, t),
+            Lerp::lerp(self.b, to.b, t),
+            Lerp::lerp(self.a, to.a, t),
+        )
+    }
+}
+
+/// A value written by tweens, that can be read from a script.
+/// Clones share the same value.
+#[derive(Debug, Clone, Default)]
+pub struct TweenTarget<T> {
+    value: Arc<Mutex<T>>,
+}
+
+impl<T: Lerp> TweenTarget<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            value: Arc::new(Mutex::new(value)),
+        }
+    }
+
+    pub fn get(&self) -> T {
+        *self.value.lock().expect("tween target poisoned")
+    }
+
+    pub fn set(&self, value: T) {
+        *self.value.lock().expect("tween target poisoned") = value;
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Animate -
+// -----------------------------------------------------------------------------
+/// Anything that is advanced over time: tweens, delays, sequences and groups.
+pub trait Animate: Send {
+    /// Advance by `delta` seconds.
+    /// Returns the part of `delta` left over after finishing, or 0.
+    fn update(&mut self, delta: f64) -> f64;
+
+    fn is_finished(&self) -> bool;
+
+    /// Start over.
+    fn reset(&mut self);
+}
+
+/// How many times to play.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repeat {
+    /// Play this many times in total
+    Count(u32),
+    Forever,
+}
+
+impl Default for Repeat {
+    fn default() -> Self {
+        Repeat::Count(1)
+    }
+}
+
+impl Repeat {
+    fn is_done(self, plays: u32) -> bool {
+        match self {
+            Repeat::Count(count) => plays >= count,
+            Repeat::Forever => false,
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Tween -
+// -----------------------------------------------------------------------------
+/// Tween a value from `from` to `to` over `duration` seconds.
+pub struct Tween<T> {
+    from: T,
+    to: T,
+    value: T,
+    duration: f64,
+    delay: f64,
+    ease: Ease,
+    repeat: Repeat,
+    yoyo: bool,
+    elapsed: f64,
+    finished: bool,
+    on_update: Option<UpdateFn<T>>,
+}
+
+impl<T: Lerp> Tween<T> {
+    pub fn new(from: T, to: T, duration: f64) -> Self {
+        Self {
+            from,
+            to,
+            value: from,
+            duration: duration.max(0.0),
+            delay: 0.0,
+            ease: Ease::Linear,
+            repeat: Repeat::default(),
+            yoyo: false,
+            elapsed: 0.0,
+            finished: false,
+            on_update: None,
+        }
+    }
+
+    pub fn with_ease(mut self, ease: Ease) -> Self {
+        self.ease = ease;
+        self
+    }
+
+    /// Wait before starting. The delay is not repeated.
+    pub fn with_delay(mut self, seconds: f64) -> Self {
+        self.delay = seconds.max(0.0);
+        self
+    }
+
+    pub fn with_repeat(mut self, repeat: Repeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    /// Play every other repeat backwards.
+    pub fn with_yoyo(mut self) -> Self {
+        self.yoyo = true;
+        self
+    }
+
+    /// Called with the new value on every update.
+    pub fn on_update<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(T) + Send + 'static,
+    {
+        self.on_update = Some(Box::new(f));
+        self
+    }
+
+    /// Write the value to a target on every update.
+    pub fn with_target(self, target: &TweenTarget<T>) -> Self {
+        let target = target.clone();
+        self.on_update(move |value| target.set(value))
+    }
+
+    pub fn value(&self) -> T {
+        self.value
+    }
+
+    /// Progress of the current play, between 0 and 1, before easing.
+    pub fn progress(&self) -> f64 {
+        let (play, t) = self.position(self.elapsed - self.delay);
+        if self.yoyo && play % 2 == 1 {
+            1.0 - t
+        } else {
+            t
+        }
+    }
+
+    // The current play, and the time within it between 0 and 1
+    fn position(&self, active: f64) -> (u64, f64) {
+        if active <= 0.0 {
+            return (0, 0.0);
+        }
+
+        if self.finished || self.duration <= 0.0 {
+            let play = match self.repeat {
+                Repeat::Count(count) => count.max(1) - 1,
+                Repeat::Forever => 0,
+            };
+            return (play as u64, 1.0);
+        }
+
+        let play = (active / self.duration).floor();
+        (play as u64, (active - play * self.duration) / self.duration)
+    }
+}
+
+impl<T: Lerp> Animate for Tween<T> {
+    fn update(&mut self, delta: f64) -> f64 {
+        if self.finished {
+            return delta;
+        }
+
+        self.elapsed += delta;
+        let active = self.elapsed - self.delay;
+        if active < 0.0 {
+            return 0.0;
+        }
+
+        let mut left_over = 0.0;
+        let total = match self.repeat {
+            Repeat::Count(count) => Some(self.duration * count.max(1) as f64),
+            Repeat::Forever if self.duration <= 0.0 => Some(0.0),
+            Repeat::Forever => None,
+        };
+
+        if let Some(total) = total {
+            if active >= total {
+                self.finished = true;
+                left_over = active - total;
+            }
+        }
+
+        let t = self.progress() as f32;
+        self.value = self.from.lerp(self.to, self.ease.apply(t));
+
+        if let Some(on_update) = self.on_update.as_mut() {
+            on_update(self.value);
+        }
+
+        left_over
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn reset(&mut self) {
+        self.elapsed = 0.0;
+        self.finished = false;
+        self.value = self.from;
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Delay and call -
+// -----------------------------------------------------------------------------
+/// Wait, e.g. between tweens in a sequence.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Delay {
+    duration: f64,
+    elapsed: f64,
+}
+
+impl Delay {
+    pub fn new(seconds: f64) -> Self {
+        Self {
+            duration: seconds.max(0.0),
+            elapsed: 0.0,
+        }
+    }
+}
+
+impl Animate for Delay {
+    fn update(&mut self, delta: f64) -> f64 {
+        self.elapsed += delta;
+        (self.elapsed - self.duration).max(0.0).min(delta)
+    }
+
+    fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    fn reset(&mut self) {
+        self.elapsed = 0.0;
+    }
+}
+
+/// Call a closure, e.g. at the end of a sequence.
+/// The closure is called again every time the sequence repeats.
+pub struct Call {
+    func: CallFn,
+    called: bool,
+}
+
+impl Call {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: FnMut() + Send + 'static,
+    {
+        Self {
+            func: Box::new(f),
+            called: false,
+        }
+    }
+}
+
+impl Animate for Call {
+    fn update(&mut self, delta: f64) -> f64 {
+        if !self.called {
+            self.called = true;
+            (self.func)();
+        }
+        delta
+    }
+
+    fn is_finished(&self) -> bool {
+        self.called
+    }
+
+    fn reset(&mut self) {
+        self.called = false;
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Sequence -
+// -----------------------------------------------------------------------------
+/// Play animations one after the other.
+#[derive(Default)]
+pub struct Sequence {
+    steps: Vec<Box<dyn Animate>>,
+    index: usize,
+    repeat: Repeat,
+    plays: u32,
+}
+
+impl Sequence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn then<A: Animate + 'static>(mut self, step: A) -> Self {
+        self.steps.push(Box::new(step));
+        self
+    }
+
+    pub fn delay(self, seconds: f64) -> Self {
+        self.then(Delay::new(seconds))
+    }
+
+    pub fn call<F>(self, f: F) -> Self
+    where
+        F: FnMut() + Send + 'static,
+    {
+        self.then(Call::new(f))
+    }
+
+    pub fn with_repeat(mut self, repeat: Repeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+}
+
+impl Animate for Sequence {
+    fn update(&mut self, mut delta: f64) -> f64 {
+        // Time left when the current play started, to stop
+        // repeating forever when a play takes no time
+        let mut play_start = delta;
+
+        loop {
+            if self.is_finished() {
+                return delta;
+            }
+
+            if self.index == self.steps.len() {
+                self.plays += 1;
+                if self.steps.is_empty() || self.repeat.is_done(self.plays) {
+                    return delta;
+                }
+
+                self.steps.iter_mut().for_each(|step| step.reset());
+                self.index = 0;
+
+                if delta <= 0.0 || delta >= play_start {
+                    return 0.0;
+                }
+                play_start = delta;
+            }
+
+            let step = &mut self.steps[self.index];
+            delta = step.update(delta);
+            if !step.is_finished() {
+                return 0.0;
+            }
+            self.index += 1;
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.index == self.steps.len() && (self.steps.is_empty() || self.repeat.is_done(self.plays))
+    }
+
+    fn reset(&mut self) {
+        self.steps.iter_mut().for_each(|step| step.reset());
+        self.index = 0;
+        self.plays = 0;
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Parallel -
+// -----------------------------------------------------------------------------
+/// Play animations at the same time.
+/// The group is finished once all animations are finished.
+#[derive(Default)]
+pub struct Parallel {
+    animations: Vec<Box<dyn Animate>>,
+    repeat: Repeat,
+    plays: u32,
+}
+
+impl Parallel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with<A: Animate + 'static>(mut self, animation: A) -> Self {
+        self.animations.push(Box::new(animation));
+        self
+    }
+
+    pub fn with_repeat(mut self, repeat: Repeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    fn is_play_finished(&self) -> bool {
+        self.animations.iter().all(|a| a.is_finished())
+    }
+}
+
+impl Animate for Parallel {
+    fn update(&mut self, mut delta: f64) -> f64 {
+        loop {
+            if self.is_finished() {
+                return delta;
+            }
+
+            let play_start = delta;
+            delta = self
+                .animations
+                .iter_mut()
+                .map(|a| a.update(play_start))
+                .fold(play_start, f64::min);
+
+            if !self.is_play_finished() {
+                return 0.0;
+            }
+
+            self.plays += 1;
+            if self.repeat.is_done(self.plays) {
+                return delta;
+            }
+
+            self.animations.iter_mut().for_each(|a| a.reset());
+            if delta <= 0.0 || delta >= play_start {
+                return 0.0;
+            }
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.animations.is_empty() || (self.is_play_finished() && self.repeat.is_done(self.plays))
+    }
+
+    fn reset(&mut self) {
+        self.animations.iter_mut().for_each(|a| a.reset());
+        self.plays = 0;
+    }
+}
+
+// -----------------------------------------------------------------------------
+//     - Tweens -
+// -----------------------------------------------------------------------------
+/// Id of an animation added to `Tweens`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TweenId(u64);
+
+/// Running animations, removed once finished.
+#[derive(Default)]
+pub struct Tweens {
+    running: Vec<(TweenId, Box<dyn Animate>)>,
+    next_id: u64,
+}
+
+impl Tweens {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add<A: Animate + 'static>(&mut self, animation: A) -> TweenId {
+        let id = TweenId(self.next_id);
+        self.next_id += 1;
+        self.running.push((id, Box::new(animation)));
+        id
+    }
+
+    /// Advance all animations, and remove the finished ones.
+    pub fn update(&mut self, delta: f64) {
+        self.running.iter_mut().for_each(|(_, a)| {
+            a.update(delta);
+        });
+        self.running.retain(|(_, a)| !a.is_finished());
+    }
+
+    /// Stop an animation where it is.
+    /// Returns false if it was already finished.
+    pub fn cancel(&mut self, id: TweenId) -> bool {
+        let len = self.running.len();
+        self.running.retain(|(running, _)| *running != id);
+        self.running.len() != len
+    }
+
+    pub fn is_running(&self, id: TweenId) -> bool {
+        self.running.iter().any(|(running, _)| *running == id)
+    }
+
+    pub fn clear(&mut self) {
+        self.running.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.running.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.running.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const CURVES: [Curve; 10] = [
+        Curve::Sine,
+        Curve::Quad,
+        Curve::Cubic,
+        Curve::Quart,
+        Curve::Quint,
+        Curve::Expo,
+        Curve::Circ,
+        Curve::Back,
+        Curve::Elastic,
+        Curve::Bounce,
+    ];
+
+    fn assert_near(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn ease_starts_at_0_and_ends_at_1() {
+        let mut eases = vec![Ease::Linear];
+        for &curve in &CURVES {
+            eases.extend(&[Ease::In(curve), Ease::Out(curve), Ease::InOut(curve)]);
+        }
+
+        for ease in eases {
+            assert_near(ease.apply(0.0) as f64, 0.0);
+            assert_near(ease.apply(1.0) as f64, 1.0);
+            // Clamped outside of 0 and 1
+            assert_near(ease.apply(-1.0) as f64, 0.0);
+            assert_near(ease.apply(2.0) as f64, 1.0);
+        }
+    }
+
+    #[test]
+    fn ease_in_midpoints() {
+        let expected = [
+            (Curve::Sine, 1.0 - std::f64::consts::FRAC_1_SQRT_2),
+            (Curve::Quad, 0.25),
+            (Curve::Cubic, 0.125),
+            (Curve::Quart, 0.0625),
+            (Curve::Quint, 0.03125),
+            (Curve::Expo, 0.03125),
+            (Curve::Circ, 1.0 - 0.75f64.sqrt()),
+            (Curve::Back, -0.087698),
+            (Curve::Elastic, -0.015625),
+            (Curve::Bounce, 0.234375),
+        ];
+
+        assert_near(Ease::Linear.apply(0.5) as f64, 0.5);
+        for &(curve, value) in &expected {
+            assert_near(Ease::In(curve).apply(0.5) as f64, value);
+        }
+    }
+
+    #[test]
+    fn ease_out_mirrors_ease_in() {
+        for &curve in &CURVES {
+            for &t in &[0.1, 0.25, 0.5, 0.8] {
+                let ease_in = Ease::In(curve).apply(1.0 - t) as f64;
+                assert_near(Ease::Out(curve).apply(t) as f64, 1.0 - ease_in);
+            }
+        }
+    }
+
+    #[test]
+    fn ease_in_out_midpoint_is_half() {
+        for &curve in &CURVES {
+            assert_near(Ease::InOut(curve).apply(0.5) as f64, 0.5);
+        }
+        assert_near(Ease::InOut(Curve::Quad).apply(0.25) as f64, 0.125);
+        assert_near(Ease::InOut(Curve::Quad).apply(0.75) as f64, 0.875);
+    }
+
+    #[test]
+    fn tween_interpolates() {
+        let mut tween = Tween::new(0.0, 10.0, 1.0);
+        assert_near(tween.update(0.25), 0.0);
+        assert_near(tween.value() as f64, 2.5);
+        assert!(!tween.is_finished());
+
+        assert_near(tween.update(1.0), 0.25);
+        assert_near(tween.value() as f64, 10.0);
+        assert!(tween.is_finished());
+        assert_near(tween.update(0.5), 0.5);
+    }
+
+    #[test]
+    fn tween_waits_for_delay() {
+        let mut tween = Tween::new(0.0, 10.0, 1.0).with_delay(0.5);
+        assert_near(tween.update(0.25), 0.0);
+        assert_near(tween.value() as f64, 0.0);
+
+        tween.update(0.5);
+        assert_near(tween.value() as f64, 2.5);
+
+        assert_near(tween.update(1.0), 0.25);
+        assert!(tween.is_finished());
+    }
+
+    #[test]
+    fn tween_repeat_count() {
+        let mut tween = Tween::new(0.0, 10.0, 1.0).with_repeat(Repeat::Count(3));
+        tween.update(2.5);
+        assert_near(tween.value() as f64, 5.0);
+        assert!(!tween.is_finished());
+
+        assert_near(tween.update(0.7), 0.2);
+        assert_near(tween.value() as f64, 10.0);
+        assert!(tween.is_finished());
+    }
+
+    #[test]
+    fn tween_repeat_forever() {
+        let mut tween = Tween::new(0.0, 10.0, 1.0).with_repeat(Repeat::Forever);
+        assert_near(tween.update(100.5), 0.0);
+        assert_near(tween.value() as f64, 5.0);
+        assert!(!tween.is_finished());
+    }
+
+    #[test]
+    fn tween_yoyo() {
+        let mut tween = Tween::new(0.0, 10.0, 1.0)
+            .with_repeat(Repeat::Count(2))
+            .with_yoyo();
+
+        let mut values = Vec::new();
+        for _ in 0..4 {
+            tween.update(0.5);
+            values.push(tween.value());
+        }
+        assert_eq!(values, [5.0, 10.0, 5.0, 0.0]);
+        assert!(tween.is_finished());
+    }
+
+    #[test]
+    fn tween_yoyo_forever() {
+        let mut tween = Tween::new(0.0, 10.0, 1.0)
+            .with_repeat(Repeat::Forever)
+            .with_yoyo();
+        tween.update(1.25);
+        assert_near(tween.value() as f64, 7.5);
+        tween.update(1.0);
+        assert_near(tween.value() as f64, 2.5);
+    }
+
+    #[test]
+    fn tween_writes_target() {
+        let target = TweenTarget::new(0.0);
+        let mut tween = Tween::new(0.0, 4.0, 2.0)
+            .with_ease(Ease::In(Curve::Quad))
+            .with_target(&target);
+
+        tween.update(1.0);
+        assert_near(target.get() as f64, 1.0);
+
+        tween.reset();
+        assert_near(tween.value() as f64, 0.0);
+        assert!(!tween.is_finished());
+    }
+
+    #[test]
+    fn sequence_passes_leftover_time_to_next_step() {
+        let target = TweenTarget::new(0.0);
+        let mut sequence = Sequence::new()
+            .then(Tween::new(0.0, 1.0, 1.0))
+            .delay(0.5)
+            .then(Tween::new(0.0, 10.0, 1.0).with_target(&target));
+
+        assert_near(sequence.update(1.75), 0.0);
+        assert_near(target.get() as f64, 2.5);
+
+        assert_near(sequence.update(1.0), 0.25);
+        assert_near(target.get() as f64, 10.0);
+        assert!(sequence.is_finished());
+    }
+
+    #[test]
+    fn sequence_calls_once_per_play() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let mut sequence = Sequence::new()
+            .delay(1.0)
+            .call(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            })
+            .with_repeat(Repeat::Count(2));
+
+        sequence.update(0.5);
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        sequence.update(1.0);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(!sequence.is_finished());
+
+        assert_near(sequence.update(1.0), 0.5);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert!(sequence.is_finished());
+    }
+
+    #[test]
+    fn sequence_of_instant_steps_does_not_loop_forever() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let mut sequence = Sequence::new()
+            .call(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            })
+            .with_repeat(Repeat::Forever);
+
+        sequence.update(1.0);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(!sequence.is_finished());
+    }
+
+    #[test]
+    fn parallel_finishes_with_longest_child() {
+        let short = TweenTarget::new(0.0);
+        let long = TweenTarget::new(0.0);
+        let mut parallel = Parallel::new()
+            .with(Tween::new(0.0, 10.0, 1.0).with_target(&short))
+            .with(Tween::new(0.0, 10.0, 3.0).with_target(&long));
+
+        assert_near(parallel.update(1.5), 0.0);
+        assert_near(short.get() as f64, 10.0);
+        assert_near(long.get() as f64, 5.0);
+        assert!(!parallel.is_finished());
+
+        assert_near(parallel.update(0.75), 0.0);
+        assert!(!parallel.is_finished());
+
+        assert_near(parallel.update(1.0), 0.25);
+        assert_near(long.get() as f64, 10.0);
+        assert!(parallel.is_finished());
+    }
+
+    #[test]
+    fn parallel_repeat() {
+        let mut parallel = Parallel::new()
+            .with(Tween::new(0.0, 1.0, 1.0))
+            .with(Tween::new(0.0, 1.0, 0.5))
+            .with_repeat(Repeat::Count(2));
+
+        parallel.update(1.5);
+        assert!(!parallel.is_finished());
+        assert_near(parallel.update(0.75), 0.25);
+        assert!(parallel.is_finished());
+    }
+
+    #[test]
+    fn tweens_drop_finished_animations() {
+        let mut tweens = Tweens::new();
+        let short = tweens.add(Tween::new(0.0, 1.0, 1.0));
+        let long = tweens.add(Tween::new(0.0, 1.0, 2.0));
+        assert_eq!(tweens.len(), 2);
+
+        tweens.update(0.5);
+        assert!(tweens.is_running(short));
+
+        tweens.update(0.5);
+        assert!(!tweens.is_running(short));
+        assert!(tweens.is_running(long));
+        assert_eq!(tweens.len(), 1);
+
+        tweens.update(1.0);
+        assert!(tweens.is_empty());
+    }
+
+    #[test]
+    fn tweens_cancel() {
+        let mut tweens = Tweens::new();
+        let id = tweens.add(Tween::new(0.0, 1.0, 1.0));
+        let other = tweens.add(Delay::new(1.0));
+
+        assert!(tweens.cancel(id));
+        assert!(!tweens.cancel(id));
+        assert!(tweens.is_running(other));
+
+        tweens.clear();
+        assert!(tweens.is_empty());
+    }
+}